@@ -2,8 +2,6 @@
 
 set -e
 
-SCRIPT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
-
 echo "Installing hindsight..."
 
 cargo build --release
@@ -17,17 +15,33 @@ echo "Installed hindsight to $INSTALL_DIR"
 "$INSTALL_DIR/hindsight" init
 echo "Database initialized"
 
-SHELL_RC="$HOME/.zshrc"
-HINDSIGHT_LINE="source $SCRIPT_DIR/shell/hindsight.zsh"
-
-if ! grep -q "hindsight.zsh" "$SHELL_RC" 2>/dev/null; then
-    echo "" >> "$SHELL_RC"
-    echo "# Hindsight shell history" >> "$SHELL_RC"
-    echo "$HINDSIGHT_LINE" >> "$SHELL_RC"
-    echo "Added hindsight to $SHELL_RC"
-else
-    echo "Hindsight already in $SHELL_RC"
+add_to_rc() {
+    local shell_rc="$1" hindsight_line="$2" shell="$3"
+
+    if ! grep -qE "hindsight init $shell|hindsight\.$shell" "$shell_rc" 2>/dev/null; then
+        echo "" >> "$shell_rc"
+        echo "# Hindsight shell history" >> "$shell_rc"
+        echo "$hindsight_line" >> "$shell_rc"
+        echo "Added hindsight to $shell_rc"
+    else
+        echo "Hindsight already in $shell_rc"
+    fi
+}
+
+SHELL_RCS=()
+if [[ -f "$HOME/.zshrc" || "$SHELL" == */zsh ]]; then
+    add_to_rc "$HOME/.zshrc" "eval \"\$($INSTALL_DIR/hindsight init zsh)\"" zsh
+    SHELL_RCS+=("$HOME/.zshrc")
+fi
+if [[ -f "$HOME/.bashrc" || "$SHELL" == */bash ]]; then
+    add_to_rc "$HOME/.bashrc" "eval \"\$($INSTALL_DIR/hindsight init bash)\"" bash
+    SHELL_RCS+=("$HOME/.bashrc")
+fi
+if [[ -d "$HOME/.config/fish" || "$SHELL" == */fish ]]; then
+    mkdir -p "$HOME/.config/fish/conf.d"
+    add_to_rc "$HOME/.config/fish/conf.d/hindsight.fish" "$INSTALL_DIR/hindsight init fish | source" fish
+    SHELL_RCS+=("$HOME/.config/fish/conf.d/hindsight.fish")
 fi
 
 echo "Installation complete!"
-echo "Restart your shell or run: source $SHELL_RC"
+echo "Restart your shell or run: source ${SHELL_RCS[*]:-<your shell rc>}"
@@ -0,0 +1,121 @@
+if [[ -z "$HINDSIGHT_SESSION" ]]; then
+  if command -v uuidgen >/dev/null 2>&1; then
+    export HINDSIGHT_SESSION=$(uuidgen)
+  else
+    export HINDSIGHT_SESSION="$$-$(date +%s)-$RANDOM"
+  fi
+fi
+export HINDSIGHT_MODE=${HINDSIGHT_MODE:-global}
+HINDSIGHT_BIN=${HINDSIGHT_BIN:-hindsight}
+HINDSIGHT_BIND=${HINDSIGHT_BIND-'\C-r'}
+
+function __hindsight_preexec() {
+  printf -v HINDSIGHT_CMD_START '%(%s)T' -1
+  HINDSIGHT_CMD=$1
+
+  "$HINDSIGHT_BIN" record-start \
+    --cwd "$PWD" \
+    --session "$HINDSIGHT_SESSION" \
+    --start "$HINDSIGHT_CMD_START" \
+    -- "$HINDSIGHT_CMD" 2>/dev/null
+}
+
+function __hindsight_precmd() {
+  local code=$?
+  if [[ -n "$HINDSIGHT_CMD" ]]; then
+    local now
+    printf -v now '%(%s)T' -1
+
+    "$HINDSIGHT_BIN" record-finish \
+      --session "$HINDSIGHT_SESSION" \
+      --start "$HINDSIGHT_CMD_START" \
+      --exit-code "$code" \
+      --duration $((now - HINDSIGHT_CMD_START)) 2>/dev/null
+
+    unset HINDSIGHT_CMD
+    unset HINDSIGHT_CMD_START
+  fi
+  return $code
+}
+
+# Without bash-preexec, the DEBUG trap fires before every simple command.
+# Only the first one after the prompt is drawn starts a new command line,
+# and `history 1` holds the whole line as typed.
+function __hindsight_prompt_ready() {
+  __hindsight_ready=1
+}
+
+function __hindsight_debug() {
+  # Completion and bind -x widgets (our own ctrl-r included) run commands
+  # while the line is still being edited; they mustn't use up the flag.
+  [[ -n "$__hindsight_ready" && -z "$COMP_LINE" && -z "${READLINE_POINT+set}" ]] || return
+  __hindsight_ready=
+
+  local line num text
+  line=$(HISTTIMEFORMAT= builtin history 1)
+  line=${line#"${line%%[![:space:]]*}"}
+  num=${line%%[!0-9]*}
+  text=${line:${#num}+2}
+
+  # No new history entry means the line wasn't saved: HISTCONTROL,
+  # HISTIGNORE or `set +o history` kept it out, so `history 1` is still the
+  # previous command. Only a repeat of that exact command is recorded.
+  if [[ "$num" == "$__hindsight_histnum" && "$text" != "$BASH_COMMAND" ]]; then
+    return
+  fi
+  __hindsight_histnum=$num
+
+  __hindsight_preexec "$text"
+}
+
+if [[ -n "${bash_preexec_imported:-}${__bp_imported:-}" ]]; then
+  preexec_functions+=(__hindsight_preexec)
+  precmd_functions=(__hindsight_precmd "${precmd_functions[@]}")
+else
+  if [[ "$(declare -p PROMPT_COMMAND 2>/dev/null)" == "declare -a"* ]]; then
+    PROMPT_COMMAND=(__hindsight_precmd "${PROMPT_COMMAND[@]}" __hindsight_prompt_ready)
+  else
+    PROMPT_COMMAND="__hindsight_precmd${PROMPT_COMMAND:+; $PROMPT_COMMAND}; __hindsight_prompt_ready"
+  fi
+  trap '__hindsight_debug' DEBUG
+fi
+
+# bind -x can't run accept-line itself, so ctrl-r runs the widget and then
+# \C-x\C-b, which the widget points at accept-line or a no-op.
+function __hindsight_widget() {
+  local selected after=redraw-current-line
+  selected=$("$HINDSIGHT_BIN" --mode "$HINDSIGHT_MODE")
+
+  if [[ "$selected" == __HINDSIGHT_MODE__* ]]; then
+    local rest=${selected#__HINDSIGHT_MODE__}
+    HINDSIGHT_MODE=${rest%%__*}
+    selected=${rest#*__}
+  fi
+
+  if [[ "$selected" == "__HINDSIGHT_EDIT__"* ]]; then
+    local cursor=
+    READLINE_LINE="${selected#__HINDSIGHT_EDIT__}"
+    if [[ "$READLINE_LINE" == __HINDSIGHT_CURSOR__* ]]; then
+      local rest=${READLINE_LINE#__HINDSIGHT_CURSOR__}
+      cursor=${rest%%__*}
+      READLINE_LINE=${rest#*__}
+    fi
+    READLINE_POINT=${cursor:-${#READLINE_LINE}}
+  elif [[ -n "$selected" ]]; then
+    READLINE_LINE="$selected"
+    READLINE_POINT=${#READLINE_LINE}
+    after=accept-line
+  fi
+
+  bind -m emacs-standard "\"\\C-x\\C-b\": $after"
+  bind -m vi-insert "\"\\C-x\\C-b\": $after"
+}
+
+for __hindsight_keymap in emacs-standard vi-insert; do
+  bind -m "$__hindsight_keymap" -x '"\C-x\C-h": __hindsight_widget'
+  bind -m "$__hindsight_keymap" '"\C-x\C-b": redraw-current-line'
+  if [[ -n "$HINDSIGHT_BIND" ]]; then
+    bind -m "$__hindsight_keymap" "\"$HINDSIGHT_BIND\": \"\\C-x\\C-h\\C-x\\C-b\""
+  fi
+done
+unset __hindsight_keymap
@@ -0,0 +1,69 @@
+if not set -q HINDSIGHT_SESSION
+    if command -sq uuidgen
+        set -gx HINDSIGHT_SESSION (uuidgen)
+    else
+        set -gx HINDSIGHT_SESSION "$fish_pid-"(date +%s)"-"(random)
+    end
+end
+set -q HINDSIGHT_MODE; or set -gx HINDSIGHT_MODE global
+set -q HINDSIGHT_BIN; or set -g HINDSIGHT_BIN hindsight
+set -q HINDSIGHT_BIND; or set -g HINDSIGHT_BIND '\cr'
+
+function __hindsight_preexec --on-event fish_preexec
+    set -g HINDSIGHT_CMD_START (date +%s)
+    set -g HINDSIGHT_CMD $argv[1]
+
+    $HINDSIGHT_BIN record-start \
+        --cwd "$PWD" \
+        --session "$HINDSIGHT_SESSION" \
+        --start "$HINDSIGHT_CMD_START" \
+        -- "$HINDSIGHT_CMD" 2>/dev/null
+end
+
+function __hindsight_postexec --on-event fish_postexec
+    set -l code $status
+    if set -q HINDSIGHT_CMD
+        # CMD_DURATION is in milliseconds; zsh and bash store whole seconds.
+        set -l duration (math --scale=0 "$CMD_DURATION / 1000")
+
+        $HINDSIGHT_BIN record-finish \
+            --session "$HINDSIGHT_SESSION" \
+            --start "$HINDSIGHT_CMD_START" \
+            --exit-code "$code" \
+            --duration "$duration" 2>/dev/null
+
+        set -e HINDSIGHT_CMD
+        set -e HINDSIGHT_CMD_START
+    end
+end
+
+function __hindsight_widget
+    set -l selected ($HINDSIGHT_BIN --mode "$HINDSIGHT_MODE" | string collect)
+
+    if string match -qr '^__HINDSIGHT_MODE__' -- "$selected"
+        set -gx HINDSIGHT_MODE (string match -r '^__HINDSIGHT_MODE__(.*?)__' -- "$selected")[2]
+        set selected (string replace -r '^__HINDSIGHT_MODE__.*?__' '' -- "$selected" | string collect)
+    end
+
+    if string match -qr '^__HINDSIGHT_EDIT__' -- "$selected"
+        set selected (string replace -r '^__HINDSIGHT_EDIT__' '' -- "$selected" | string collect)
+        set -l cursor (string match -r '^__HINDSIGHT_CURSOR__(\d+)__' -- "$selected")[2]
+        set selected (string replace -r '^__HINDSIGHT_CURSOR__\d+__' '' -- "$selected" | string collect)
+        commandline -r -- "$selected"
+        if test -n "$cursor"
+            commandline -C $cursor
+        else
+            commandline -f end-of-buffer
+        end
+    else if test -n "$selected"
+        commandline -r -- "$selected"
+        commandline -f execute
+    end
+    commandline -f repaint
+end
+
+if test -n "$HINDSIGHT_BIND"
+    set -l key (string unescape -- $HINDSIGHT_BIND)
+    bind $key __hindsight_widget
+    bind -M insert $key __hindsight_widget 2>/dev/null
+end
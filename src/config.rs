@@ -2,11 +2,50 @@ use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
 use std::fs;
 
+use crate::ignore::IgnoreConfig;
+use crate::secrets::SecretsConfig;
+
 #[derive(Debug, Serialize, Deserialize, Default)]
 pub struct Config {
     pub default_mode: Option<String>,
+    pub default_rank: Option<String>,
     pub default_limit: Option<u32>,
+    pub default_exit_filter: Option<String>,
     pub height: Option<String>,
+    pub show_preview: Option<bool>,
+    pub tree_git_root: Option<bool>,
+    #[serde(default)]
+    pub frecency: FrecencyWeights,
+    #[serde(default)]
+    pub ignore: IgnoreConfig,
+    #[serde(default)]
+    pub secrets: SecretsConfig,
+}
+
+/// Weights for the `frecency` rank. Each component is scaled to 0..1
+/// before weighting.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FrecencyWeights {
+    pub frequency: f64,
+    pub recency: f64,
+    pub cwd: f64,
+    pub session: f64,
+    pub host: f64,
+    pub success: f64,
+}
+
+impl Default for FrecencyWeights {
+    fn default() -> Self {
+        FrecencyWeights {
+            frequency: 1.0,
+            recency: 1.0,
+            cwd: 0.5,
+            session: 0.3,
+            host: 0.2,
+            success: 0.5,
+        }
+    }
 }
 
 impl Config {
@@ -24,4 +63,27 @@ impl Config {
         
         config
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partial_frecency_table_keeps_defaults() {
+        let config: Config = toml::from_str(
+            "default_rank = \"frecency\"\n[frecency]\ncwd = 2.0\n",
+        ).unwrap();
+
+        assert_eq!(config.default_rank.as_deref(), Some("frecency"));
+        assert_eq!(config.frecency.cwd, 2.0);
+        assert_eq!(config.frecency.frequency, 1.0);
+        assert_eq!(config.frecency.success, 0.5);
+    }
+
+    #[test]
+    fn test_missing_frecency_table_uses_defaults() {
+        let config: Config = toml::from_str("default_mode = \"cwd\"\n").unwrap();
+        assert_eq!(config.frecency.recency, 1.0);
+    }
+}
@@ -0,0 +1,369 @@
+use crossbeam_channel::{unbounded, Receiver, RecvTimeoutError};
+use directories::ProjectDirs;
+use std::io::{self, BufReader, Read};
+use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::db::{Database, HistoryEntry};
+use crate::ignore::IgnoreRules;
+use crate::secrets::SecretPolicy;
+
+const MAX_FRAME_LEN: usize = 1 << 20;
+const MAX_BATCH: usize = 256;
+const BATCH_WINDOW: Duration = Duration::from_millis(50);
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// A request from the shell hook. Frames are netstrings (`<len>:<payload>,`)
+/// whose payload is a NUL-separated list of fields:
+///
+/// - `start \0 session \0 start_ts \0 cwd \0 command`
+/// - `finish \0 session \0 start_ts \0 exit_code \0 duration`
+#[derive(Debug, PartialEq)]
+pub enum Message {
+    Start {
+        session: String,
+        start_ts: i64,
+        cwd: String,
+        command: String,
+    },
+    Finish {
+        session: String,
+        start_ts: i64,
+        exit_code: i32,
+        duration: i64,
+    },
+}
+
+impl Message {
+    pub fn parse(payload: &[u8]) -> Option<Self> {
+        let payload = String::from_utf8_lossy(payload);
+        let fields: Vec<&str> = payload.splitn(5, '\0').collect();
+        match fields.as_slice() {
+            ["start", session, start_ts, cwd, command] => Some(Message::Start {
+                session: session.to_string(),
+                start_ts: start_ts.parse().ok()?,
+                cwd: cwd.to_string(),
+                command: command.to_string(),
+            }),
+            ["finish", session, start_ts, exit_code, duration] => Some(Message::Finish {
+                session: session.to_string(),
+                start_ts: start_ts.parse().ok()?,
+                exit_code: exit_code.parse().ok()?,
+                duration: duration.parse().ok()?,
+            }),
+            _ => None,
+        }
+    }
+
+    #[cfg(test)]
+    fn encode(&self) -> Vec<u8> {
+        let payload = match self {
+            Message::Start { session, start_ts, cwd, command } => {
+                format!("start\0{}\0{}\0{}\0{}", session, start_ts, cwd, command)
+            }
+            Message::Finish { session, start_ts, exit_code, duration } => {
+                format!("finish\0{}\0{}\0{}\0{}", session, start_ts, exit_code, duration)
+            }
+        };
+        let mut frame = format!("{}:", payload.len()).into_bytes();
+        frame.extend_from_slice(payload.as_bytes());
+        frame.push(b',');
+        frame
+    }
+}
+
+pub fn socket_path() -> io::Result<PathBuf> {
+    if let Ok(path) = std::env::var("HINDSIGHT_SOCKET") {
+        if !path.is_empty() {
+            return Ok(PathBuf::from(path));
+        }
+    }
+
+    // Prefer a local runtime directory: unix sockets don't work on NFS homes.
+    if let Some(dir) = ProjectDirs::from("com", "shaarawi", "hindsight")
+        .and_then(|p| p.runtime_dir().map(Path::to_path_buf))
+    {
+        if std::fs::create_dir_all(&dir).is_ok() {
+            return Ok(dir.join("daemon.sock"));
+        }
+    }
+
+    // The temp dir is shared, so the socket goes in a directory only we
+    // can use; anyone else could otherwise listen in on every command.
+    let uid = unsafe { libc::geteuid() };
+    let dir = std::env::temp_dir().join(format!("hindsight-{}", uid));
+    private_dir(&dir, uid)?;
+    Ok(dir.join("daemon.sock"))
+}
+
+/// Creates `dir` with mode 0700 if needed, then checks that it is a real
+/// directory owned by `uid` that nobody else can get into.
+fn private_dir(dir: &Path, uid: u32) -> io::Result<()> {
+    match std::fs::DirBuilder::new().mode(0o700).create(dir) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+        Err(e) => return Err(e),
+    }
+    let meta = std::fs::symlink_metadata(dir)?;
+    if !meta.is_dir() || meta.uid() != uid || meta.mode() & 0o077 != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{:?} must be a directory owned by you with mode 0700", dir),
+        ));
+    }
+    Ok(())
+}
+
+/// Reads one netstring frame, returning `None` on a clean end of stream.
+fn read_frame<R: Read>(reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut len = 0usize;
+    let mut saw_digit = false;
+    let mut byte = [0u8; 1];
+
+    loop {
+        if reader.read(&mut byte)? == 0 {
+            if saw_digit {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            return Ok(None);
+        }
+        match byte[0] {
+            b'0'..=b'9' => {
+                len = len * 10 + (byte[0] - b'0') as usize;
+                saw_digit = true;
+                if len > MAX_FRAME_LEN {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, "frame too large"));
+                }
+            }
+            b':' if saw_digit => break,
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "malformed frame length")),
+        }
+    }
+
+    let mut payload = vec![0u8; len];
+    reader.read_exact(&mut payload)?;
+    reader.read_exact(&mut byte)?;
+    if byte[0] != b',' {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "missing frame terminator"));
+    }
+    Ok(Some(payload))
+}
+
+pub fn run(path: &Path) -> io::Result<()> {
+    if path.exists() {
+        if UnixStream::connect(path).is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                format!("daemon already listening on {:?}", path),
+            ));
+        }
+        std::fs::remove_file(path)?;
+    }
+
+    // Created 0600 from the start, so there's no moment anyone else can
+    // connect.
+    let umask = unsafe { libc::umask(0o177) };
+    let listener = UnixListener::bind(path);
+    unsafe { libc::umask(umask) };
+    let listener = listener?;
+
+    let config = Config::load();
+    let db = Database::new()
+        .map_err(io::Error::other)?
+        .with_ignore_rules(IgnoreRules::new(&config.ignore))
+        .with_secrets(SecretPolicy::new(&config.secrets));
+    let (sender, receiver) = unbounded();
+    let writer = thread::spawn(move || write_batches(&db, receiver));
+
+    // Connections are drained one at a time so a command's start always
+    // reaches the writer before its finish.
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("hindsight daemon: accept failed: {}", e);
+                continue;
+            }
+        };
+        let _ = stream.set_read_timeout(Some(CLIENT_TIMEOUT));
+        let mut reader = BufReader::new(stream);
+
+        loop {
+            match read_frame(&mut reader) {
+                Ok(Some(payload)) => match Message::parse(&payload) {
+                    Some(message) => {
+                        if sender.send(message).is_err() {
+                            break;
+                        }
+                    }
+                    None => eprintln!("hindsight daemon: ignoring malformed message"),
+                },
+                Ok(None) => break,
+                Err(e) => {
+                    eprintln!("hindsight daemon: bad frame: {}", e);
+                    break;
+                }
+            }
+        }
+    }
+
+    drop(sender);
+    let _ = writer.join();
+    Ok(())
+}
+
+fn write_batches(db: &Database, receiver: Receiver<Message>) {
+    while let Ok(first) = receiver.recv() {
+        let mut batch = vec![first];
+        let deadline = Instant::now() + BATCH_WINDOW;
+
+        while batch.len() < MAX_BATCH {
+            match receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(message) => batch.push(message),
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+
+        if let Err(e) = apply_batch(db, &batch) {
+            eprintln!("hindsight daemon: failed to write {} records: {}", batch.len(), e);
+        }
+    }
+}
+
+fn apply_batch(db: &Database, batch: &[Message]) -> rusqlite::Result<()> {
+    let hostname = hostname::get()
+        .map(|h| h.to_string_lossy().to_string())
+        .unwrap_or_else(|_| "unknown".to_string());
+
+    db.transaction(|db| {
+        for message in batch {
+            let result = match message {
+                Message::Start { session, start_ts, cwd, command } => db.record_command(&HistoryEntry {
+                    command: command.clone(),
+                    exit_code: None,
+                    cwd: Some(cwd.clone()),
+                    hostname: hostname.clone(),
+                    session: session.clone(),
+                    start_ts: *start_ts,
+                    duration: None,
+                }),
+                Message::Finish { session, start_ts, exit_code, duration } => {
+                    db.finish_command(session, *start_ts, *exit_code, *duration)
+                }
+            };
+            if let Err(e) = result {
+                eprintln!("hindsight daemon: {}", e);
+            }
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn start_message() -> Message {
+        Message::Start {
+            session: "abc".to_string(),
+            start_ts: 1706384400,
+            cwd: "/tmp".to_string(),
+            command: "echo 'a;b'\nls".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_private_dir_checks_owner_and_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let root = tempfile::tempdir().unwrap();
+        let uid = unsafe { libc::geteuid() };
+        let dir = root.path().join("sock");
+        private_dir(&dir, uid).unwrap();
+        assert_eq!(std::fs::metadata(&dir).unwrap().mode() & 0o777, 0o700);
+        private_dir(&dir, uid).unwrap();
+
+        assert!(private_dir(&dir, uid + 1).is_err());
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(private_dir(&dir, uid).is_err());
+
+        let link = root.path().join("link");
+        std::os::unix::fs::symlink(&dir, &link).unwrap();
+        assert!(private_dir(&link, uid).is_err());
+    }
+
+    #[test]
+    fn test_frame_round_trip() {
+        let finish = Message::Finish {
+            session: "abc".to_string(),
+            start_ts: 1706384400,
+            exit_code: -1,
+            duration: 12,
+        };
+        let mut bytes = start_message().encode();
+        bytes.extend(finish.encode());
+
+        let mut reader = Cursor::new(bytes);
+        let first = read_frame(&mut reader).unwrap().unwrap();
+        let second = read_frame(&mut reader).unwrap().unwrap();
+        assert_eq!(Message::parse(&first), Some(start_message()));
+        assert_eq!(Message::parse(&second), Some(finish));
+        assert_eq!(read_frame(&mut reader).unwrap(), None);
+    }
+
+    #[test]
+    fn test_frame_counts_bytes_not_chars() {
+        let message = Message::Start {
+            session: "s".to_string(),
+            start_ts: 1,
+            cwd: "/tmp".to_string(),
+            command: "echo ü".to_string(),
+        };
+        let mut reader = Cursor::new(message.encode());
+        let payload = read_frame(&mut reader).unwrap().unwrap();
+        assert_eq!(Message::parse(&payload), Some(message));
+    }
+
+    #[test]
+    fn test_malformed_frames_rejected() {
+        assert!(read_frame(&mut Cursor::new(b"x:abc,".to_vec())).is_err());
+        assert!(read_frame(&mut Cursor::new(b"3:abc;".to_vec())).is_err());
+        assert!(read_frame(&mut Cursor::new(b"10:abc".to_vec())).is_err());
+        assert!(read_frame(&mut Cursor::new(b"99999999:".to_vec())).is_err());
+    }
+
+    #[test]
+    fn test_parse_rejects_unknown_messages() {
+        assert_eq!(Message::parse(b"stop\0abc"), None);
+        assert_eq!(Message::parse(b"finish\x00abc\x00notanumber\x000\x001"), None);
+        assert_eq!(Message::parse(b"start\x00abc\x001"), None);
+    }
+
+    #[test]
+    fn test_apply_batch_starts_and_finishes() {
+        let db = Database::in_memory().unwrap();
+        let batch = vec![
+            start_message(),
+            Message::Finish {
+                session: "abc".to_string(),
+                start_ts: 1706384400,
+                exit_code: 3,
+                duration: 2,
+            },
+        ];
+
+        apply_batch(&db, &batch).unwrap();
+
+        let history = db.history_entries().unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].command, "echo 'a;b'\nls");
+        assert_eq!(history[0].exit_code, Some(3));
+        assert_eq!(history[0].duration, Some(2));
+    }
+}
@@ -0,0 +1,322 @@
+use chrono::{Local, NaiveDate, NaiveDateTime, TimeZone};
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crate::db::{HistoryEntry, SavedCommand};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Jsonl,
+    Csv,
+    Zsh,
+    Bash,
+}
+
+impl ExportFormat {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "jsonl" => Some(ExportFormat::Jsonl),
+            "csv" => Some(ExportFormat::Csv),
+            "zsh" => Some(ExportFormat::Zsh),
+            "bash" => Some(ExportFormat::Bash),
+            _ => None,
+        }
+    }
+}
+
+/// Parses `--since`/`--until`: epoch seconds, or a local `YYYY-MM-DD` date
+/// with an optional `HH:MM:SS` time.
+pub fn parse_time(s: &str) -> Option<i64> {
+    if let Ok(ts) = s.parse::<i64>() {
+        return Some(ts);
+    }
+    let naive = NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .ok()
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .or_else(|| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").ok())
+        .or_else(|| NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S").ok())?;
+    Local.from_local_datetime(&naive).earliest().map(|dt| dt.timestamp())
+}
+
+/// Writes history rows. `paths` are the paths fish recorded per row id;
+/// only JSON Lines has room for them.
+pub fn write_history<W: Write>(
+    out: &mut W,
+    format: ExportFormat,
+    rows: &[(i64, HistoryEntry)],
+    paths: &HashMap<i64, Vec<String>>,
+) -> io::Result<()> {
+    if format == ExportFormat::Csv {
+        writeln!(out, "id,command,exit_code,cwd,hostname,session,start_ts,duration")?;
+    }
+
+    for (id, entry) in rows {
+        match format {
+            ExportFormat::Jsonl => {
+                let paths: Vec<String> = paths.get(id).into_iter().flatten().map(|p| json_string(p)).collect();
+                writeln!(
+                    out,
+                    "{{\"type\":\"history\",\"id\":{},\"command\":{},\"exit_code\":{},\"cwd\":{},\"hostname\":{},\"session\":{},\"start_ts\":{},\"duration\":{},\"paths\":[{}]}}",
+                    id,
+                    json_string(&entry.command),
+                    json_opt(entry.exit_code),
+                    json_opt(entry.cwd.as_deref().map(json_string)),
+                    json_string(&entry.hostname),
+                    json_string(&entry.session),
+                    entry.start_ts,
+                    json_opt(entry.duration),
+                    paths.join(","),
+                )?
+            }
+            ExportFormat::Csv => writeln!(
+                out,
+                "{},{},{},{},{},{},{},{}",
+                id,
+                csv_field(&entry.command),
+                csv_opt(entry.exit_code),
+                csv_field(entry.cwd.as_deref().unwrap_or("")),
+                csv_field(&entry.hostname),
+                csv_field(&entry.session),
+                entry.start_ts,
+                csv_opt(entry.duration),
+            )?,
+            ExportFormat::Zsh => {
+                // zsh marks embedded newlines with a trailing backslash and
+                // stores the file metafied.
+                let command = entry.command.replace('\n', "\\\n");
+                out.write_all(format!(": {}:{};", entry.start_ts, entry.duration.unwrap_or(0)).as_bytes())?;
+                out.write_all(&metafy(command.as_bytes()))?;
+                out.write_all(b"\n")?;
+            }
+            ExportFormat::Bash => writeln!(out, "#{}\n{}", entry.start_ts, entry.command)?,
+        }
+    }
+    Ok(())
+}
+
+/// Writes the saved-command library: tags, then commands with their tag
+/// names. Only JSON Lines and CSV can hold it.
+pub fn write_saved<W: Write>(
+    out: &mut W,
+    format: ExportFormat,
+    tags: &[(i64, String)],
+    saved: &[SavedCommand],
+) -> io::Result<()> {
+    match format {
+        ExportFormat::Jsonl => {
+            for (id, name) in tags {
+                writeln!(out, "{{\"type\":\"tag\",\"id\":{},\"name\":{}}}", id, json_string(name))?;
+            }
+            for cmd in saved {
+                let tags: Vec<String> = cmd.tags.iter().map(|t| json_string(t)).collect();
+                writeln!(
+                    out,
+                    "{{\"type\":\"saved\",\"id\":{},\"command\":{},\"description\":{},\"created_at\":{},\"tags\":[{}]}}",
+                    cmd.id,
+                    json_string(&cmd.command),
+                    json_opt(cmd.description.as_deref().map(json_string)),
+                    cmd.created_at,
+                    tags.join(","),
+                )?;
+            }
+        }
+        ExportFormat::Csv => {
+            writeln!(out, "id,command,description,created_at,tags")?;
+            for cmd in saved {
+                writeln!(
+                    out,
+                    "{},{},{},{},{}",
+                    cmd.id,
+                    csv_field(&cmd.command),
+                    csv_field(cmd.description.as_deref().unwrap_or("")),
+                    cmd.created_at,
+                    csv_field(&cmd.tags.join(",")),
+                )?;
+            }
+        }
+        ExportFormat::Zsh | ExportFormat::Bash => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "saved commands can only be exported as jsonl or csv",
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn json_opt<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_else(|| "null".to_string())
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+fn csv_opt<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+/// The inverse of `Database::unmetafy`.
+fn metafy(bytes: &[u8]) -> Vec<u8> {
+    const META: u8 = 0x83;
+    let mut out = Vec::with_capacity(bytes.len());
+    for &b in bytes {
+        if b == 0 || (META..=0xa2).contains(&b) {
+            out.push(META);
+            out.push(b ^ 0x20);
+        } else {
+            out.push(b);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(command: &str, exit_code: Option<i32>, duration: Option<i64>) -> HistoryEntry {
+        HistoryEntry {
+            command: command.to_string(),
+            exit_code,
+            cwd: Some("/src".to_string()),
+            hostname: "laptop".to_string(),
+            session: "s1".to_string(),
+            start_ts: 1706384400,
+            duration,
+        }
+    }
+
+    fn export(format: ExportFormat, rows: &[(i64, HistoryEntry)]) -> String {
+        let mut out = Vec::new();
+        write_history(&mut out, format, rows, &HashMap::new()).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_jsonl_escapes_and_nulls() {
+        let rows = vec![(7, entry("echo \"a\\b\"\n\tls", None, None))];
+        assert_eq!(
+            export(ExportFormat::Jsonl, &rows),
+            "{\"type\":\"history\",\"id\":7,\"command\":\"echo \\\"a\\\\b\\\"\\n\\tls\",\"exit_code\":null,\
+             \"cwd\":\"/src\",\"hostname\":\"laptop\",\"session\":\"s1\",\"start_ts\":1706384400,\"duration\":null,\"paths\":[]}\n"
+        );
+    }
+
+    #[test]
+    fn test_jsonl_control_characters_and_non_bmp() {
+        assert_eq!(json_string("a\u{1b}[0m\u{0}\u{8}\u{c}\r\u{1f}"), "\"a\\u001b[0m\\u0000\\u0008\\u000c\\r\\u001f\"");
+        // Above the control range and outside the BMP, characters go out
+        // as UTF-8 unchanged, which JSON allows.
+        assert_eq!(json_string("\u{7f} 🦀 \u{2028}"), "\"\u{7f} 🦀 \u{2028}\"");
+    }
+
+    #[test]
+    fn test_jsonl_includes_fish_paths() {
+        let rows = vec![(2, entry("vim notes.md", Some(0), Some(1)))];
+        let paths = HashMap::from([(2, vec!["/src/notes.md".to_string(), "/tmp/x\"y".to_string()])]);
+        let mut out = Vec::new();
+        write_history(&mut out, ExportFormat::Jsonl, &rows, &paths).unwrap();
+        assert!(String::from_utf8(out).unwrap().ends_with(",\"paths\":[\"/src/notes.md\",\"/tmp/x\\\"y\"]}\n"));
+    }
+
+    #[test]
+    fn test_csv_control_characters_and_non_bmp() {
+        assert_eq!(csv_field("a\u{1b}[0m\tb 🦀"), "a\u{1b}[0m\tb 🦀");
+        assert_eq!(csv_field("a\rb"), "\"a\rb\"");
+        assert_eq!(csv_field("🦀,\""), "\"🦀,\"\"\"");
+    }
+
+    #[test]
+    fn test_csv_quotes_fields() {
+        let rows = vec![(1, entry("echo \"a,b\"", Some(1), Some(3)))];
+        assert_eq!(
+            export(ExportFormat::Csv, &rows),
+            "id,command,exit_code,cwd,hostname,session,start_ts,duration\n\
+             1,\"echo \"\"a,b\"\"\",1,/src,laptop,s1,1706384400,3\n"
+        );
+    }
+
+    #[test]
+    fn test_shell_formats() {
+        let rows = vec![(1, entry("for i in 1 2; do\necho $i\ndone", Some(0), Some(4)))];
+        assert_eq!(
+            export(ExportFormat::Zsh, &rows),
+            ": 1706384400:4;for i in 1 2; do\\\necho $i\\\ndone\n"
+        );
+        assert_eq!(
+            export(ExportFormat::Bash, &rows),
+            "#1706384400\nfor i in 1 2; do\necho $i\ndone\n"
+        );
+    }
+
+    #[test]
+    fn test_zsh_export_is_metafied() {
+        let rows = vec![(1, entry("echo →", Some(0), Some(0)))];
+        let mut out = Vec::new();
+        write_history(&mut out, ExportFormat::Zsh, &rows, &HashMap::new()).unwrap();
+        assert_eq!(out, b": 1706384400:0;echo \xe2\x83\xa6\x83\xb2\n");
+    }
+
+    #[test]
+    fn test_saved_export() {
+        let saved = vec![SavedCommand {
+            id: 3,
+            command: "docker ps -a".to_string(),
+            description: Some("List \"all\"".to_string()),
+            created_at: 1706384400,
+            tags: vec!["docker".to_string(), "ops".to_string()],
+        }];
+        let tags = vec![(1, "docker".to_string()), (2, "ops".to_string())];
+
+        let mut out = Vec::new();
+        write_saved(&mut out, ExportFormat::Jsonl, &tags, &saved).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "{\"type\":\"tag\",\"id\":1,\"name\":\"docker\"}\n\
+             {\"type\":\"tag\",\"id\":2,\"name\":\"ops\"}\n\
+             {\"type\":\"saved\",\"id\":3,\"command\":\"docker ps -a\",\"description\":\"List \\\"all\\\"\",\
+             \"created_at\":1706384400,\"tags\":[\"docker\",\"ops\"]}\n"
+        );
+
+        let mut out = Vec::new();
+        write_saved(&mut out, ExportFormat::Csv, &tags, &saved).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "id,command,description,created_at,tags\n3,docker ps -a,\"List \"\"all\"\"\",1706384400,\"docker,ops\"\n"
+        );
+
+        assert!(write_saved(&mut Vec::new(), ExportFormat::Bash, &tags, &saved).is_err());
+    }
+
+    #[test]
+    fn test_parse_time() {
+        assert_eq!(parse_time("1706384400"), Some(1706384400));
+        let day = parse_time("2024-01-27").unwrap();
+        assert_eq!(parse_time("2024-01-27 00:00:10"), Some(day + 10));
+        assert_eq!(parse_time("2024-01-27T01:00:00"), Some(day + 3600));
+        assert_eq!(parse_time("yesterday"), None);
+    }
+}
@@ -0,0 +1,171 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// The `[ignore]` table in the config file: commands that are never
+/// recorded or imported.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IgnoreConfig {
+    /// Skip commands typed with a leading space, like zsh's
+    /// HIST_IGNORE_SPACE.
+    pub leading_space: bool,
+    /// Regexes, matched anywhere in the command.
+    pub patterns: Vec<String>,
+    /// Shell-style globs (`*`, `?`), matched against the whole command.
+    pub globs: Vec<String>,
+    /// Directories whose commands are skipped, subdirectories included.
+    /// A leading `~` is the home directory.
+    pub directories: Vec<String>,
+    /// Skip commands shorter than this many characters.
+    pub min_length: usize,
+}
+
+impl Default for IgnoreConfig {
+    fn default() -> Self {
+        IgnoreConfig {
+            leading_space: true,
+            patterns: Vec::new(),
+            globs: Vec::new(),
+            directories: Vec::new(),
+            min_length: 0,
+        }
+    }
+}
+
+pub struct IgnoreRules {
+    leading_space: bool,
+    patterns: Vec<Regex>,
+    directories: Vec<String>,
+    min_length: usize,
+}
+
+impl IgnoreRules {
+    /// Compiles the config. An invalid pattern is reported on stderr and
+    /// left out.
+    pub fn new(config: &IgnoreConfig) -> Self {
+        let regexes = config.patterns.iter().cloned();
+        let globs = config.globs.iter().map(|glob| glob_to_regex(glob));
+        let patterns = regexes
+            .chain(globs)
+            .filter_map(|pattern| match Regex::new(&pattern) {
+                Ok(re) => Some(re),
+                Err(e) => {
+                    eprintln!("hindsight: ignoring ignore pattern '{}': {}", pattern, e);
+                    None
+                }
+            })
+            .collect();
+
+        let home = dirs::home_dir().map(|h| h.to_string_lossy().to_string());
+        let directories = config
+            .directories
+            .iter()
+            .map(|dir| match (dir.strip_prefix('~'), &home) {
+                (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => format!("{}{}", home, rest),
+                _ => dir.clone(),
+            })
+            .map(|dir| dir.trim_end_matches('/').to_string())
+            .collect();
+
+        IgnoreRules {
+            leading_space: config.leading_space,
+            patterns,
+            directories,
+            min_length: config.min_length,
+        }
+    }
+
+    /// Whether a command run in `cwd` should be left out of history.
+    /// Imported commands often have no directory; only the other rules
+    /// apply to them.
+    pub fn ignores(&self, command: &str, cwd: Option<&str>) -> bool {
+        if self.leading_space && command.starts_with([' ', '\t']) {
+            return true;
+        }
+        if command.trim().chars().count() < self.min_length {
+            return true;
+        }
+        if self.patterns.iter().any(|re| re.is_match(command)) {
+            return true;
+        }
+        cwd.is_some_and(|cwd| {
+            self.directories
+                .iter()
+                .any(|dir| cwd == dir || cwd.strip_prefix(dir.as_str()).is_some_and(|rest| rest.starts_with('/')))
+        })
+    }
+}
+
+/// `*` is any run of characters and `?` any one; everything else is
+/// literal.
+fn glob_to_regex(glob: &str) -> String {
+    let mut re = String::from("(?s)^");
+    for c in glob.chars() {
+        match c {
+            '*' => re.push_str(".*"),
+            '?' => re.push('.'),
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    re.push('$');
+    re
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(toml: &str) -> IgnoreRules {
+        IgnoreRules::new(&toml::from_str(toml).unwrap())
+    }
+
+    #[test]
+    fn test_leading_space_on_by_default() {
+        let default = rules("");
+        assert!(default.ignores(" export TOKEN=x", None));
+        assert!(!default.ignores("ls", None));
+        assert!(!rules("leading_space = false").ignores(" ls", None));
+    }
+
+    #[test]
+    fn test_patterns_and_globs() {
+        let rules = rules(
+            r#"
+            patterns = ["^(ls|pwd)$", "("]
+            globs = ["cd *", "git st?tus"]
+            "#,
+        );
+        assert!(rules.ignores("ls", None));
+        assert!(!rules.ignores("ls -la", None));
+        assert!(rules.ignores("cd /tmp", None));
+        assert!(!rules.ignores("echo cd /tmp", None));
+        assert!(rules.ignores("git status", None));
+        assert!(!rules.ignores("git stash", None));
+    }
+
+    #[test]
+    fn test_directories_include_subdirectories() {
+        let rules = rules(r#"directories = ["/vault/", "~/private"]"#);
+        assert!(rules.ignores("cat key", Some("/vault")));
+        assert!(rules.ignores("cat key", Some("/vault/a/b")));
+        assert!(!rules.ignores("cat key", Some("/vaults")));
+        assert!(!rules.ignores("cat key", None));
+        if let Some(home) = dirs::home_dir() {
+            let private = home.join("private").join("x");
+            assert!(rules.ignores("ls", Some(&private.to_string_lossy())));
+        }
+    }
+
+    #[test]
+    fn test_min_length() {
+        let rules = rules("min_length = 3");
+        assert!(rules.ignores("ls", None));
+        assert!(rules.ignores("l  ", None));
+        assert!(!rules.ignores("pwd", None));
+    }
+
+    #[test]
+    fn test_glob_to_regex_escapes() {
+        assert_eq!(glob_to_regex("a.b*"), "(?s)^a\\.b.*$");
+    }
+}
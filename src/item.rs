@@ -1,7 +1,8 @@
 use skim::prelude::*;
 use ratatui::text::Line;
 use std::borrow::Cow;
-use crate::db::{HistoryRecord, SavedCommand};
+use std::sync::Mutex;
+use crate::db::{CommandStats, CommandStatus, Database, HistoryRecord, SavedCommand};
 use chrono::Utc;
 
 fn format_duration(seconds: i64) -> String {
@@ -38,8 +39,53 @@ fn format_age(timestamp: i64) -> String {
     format!("{} ago", unit)
 }
 
+fn status_marker(record: &HistoryRecord) -> String {
+    match (record.status, record.exit_code) {
+        (CommandStatus::Running, _) => "[running] ".to_string(),
+        (CommandStatus::Unknown, _) => "[unknown exit] ".to_string(),
+        (CommandStatus::Finished, Some(code)) if code != 0 => format!("[exit {}] ", code),
+        (CommandStatus::Finished, _) => String::new(),
+    }
+}
+
+fn format_timestamp(timestamp: i64) -> String {
+    chrono::DateTime::from_timestamp(timestamp, 0)
+        .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_else(|| "Unknown".to_string())
+}
+
+fn push_counts<T: ToString>(out: &mut String, title: &str, counts: &[(Option<T>, i64)]) {
+    out.push_str(&format!("\n{}:\n", title));
+    for (value, count) in counts {
+        let value = value.as_ref().map(|v| v.to_string()).unwrap_or_else(|| "-".to_string());
+        out.push_str(&format!("  {:>5}  {}\n", count, value));
+    }
+}
+
+fn format_stats(stats: &CommandStats) -> String {
+    let avg = stats.avg_duration
+        .map(|d| format!(" (avg {})", format_duration(d.round() as i64)))
+        .unwrap_or_default();
+    let mut out = format!(
+        "Runs: {}{} across {} session(s)\nFirst seen: {}\nLast seen: {}\nLast session: {}\n",
+        stats.runs,
+        avg,
+        stats.sessions,
+        format_timestamp(stats.first_seen),
+        format_timestamp(stats.last_seen),
+        stats.last_session.as_deref().unwrap_or("Unknown"),
+    );
+
+    push_counts(&mut out, "Directories", &stats.directories);
+    push_counts(&mut out, "Exit codes", &stats.exit_codes);
+    push_counts(&mut out, "Hosts", &stats.hosts);
+
+    out
+}
+
 pub struct HistoryItem {
     pub record: HistoryRecord,
+    pub db: Option<Arc<Mutex<Database>>>,
 }
 
 impl SkimItem for HistoryItem {
@@ -48,34 +94,52 @@ impl SkimItem for HistoryItem {
     }
 
     fn display<'a>(&'a self, _context: DisplayContext) -> Line<'a> {
-        let duration = format_duration(self.record.duration);
+        let duration = match self.record.status {
+            CommandStatus::Finished => format_duration(self.record.duration),
+            CommandStatus::Running | CommandStatus::Unknown => "-".to_string(),
+        };
+        let marker = status_marker(&self.record);
         let age = format_age(self.record.timestamp);
         let cmd: String = self.record.command
             .chars()
             .map(|c| if c.is_control() { ' ' } else { c })
             .take(200)
             .collect();
-        let display_str = format!("{:<5} {:>10}  {}", duration, age, cmd);
+        let display_str = format!("{:<5} {:>10}  {}{}", duration, age, marker, cmd);
         Line::raw(display_str)
     }
 
     fn preview(&self, _context: PreviewContext) -> ItemPreview {
         let duration_secs = self.record.duration;
-        let duration_str = if duration_secs < 60 {
-            format!("{}s", duration_secs)
-        } else {
-            format!("{}m {}s", duration_secs / 60, duration_secs % 60)
+        let duration_str = match self.record.status {
+            CommandStatus::Running => "running".to_string(),
+            CommandStatus::Unknown => "unknown exit".to_string(),
+            CommandStatus::Finished if duration_secs < 60 => format!("{}s", duration_secs),
+            CommandStatus::Finished => format!("{}m {}s", duration_secs / 60, duration_secs % 60),
         };
 
-        let timestamp = chrono::DateTime::from_timestamp(self.record.timestamp, 0)
-            .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
-            .unwrap_or_else(|| "Unknown".to_string());
+        let timestamp = format_timestamp(self.record.timestamp);
+
+        let exit_str = self.record.exit_code
+            .map(|c| c.to_string())
+            .unwrap_or_else(|| "-".to_string());
+
+        let host_str = self.record.hostname.as_deref().unwrap_or("Unknown");
 
-        let preview = format!(
-            "Command: {}\nExecuted: {}\nDuration: {}",
-            self.record.command, timestamp, duration_str
+        let mut preview = format!(
+            "Command: {}\nExecuted: {}\nDuration: {}\nExit: {}\nHost: {}\n",
+            self.record.command, timestamp, duration_str, exit_str, host_str
         );
 
+        let stats = self.db.as_ref().and_then(|db| {
+            let db = db.lock().ok()?;
+            db.command_stats(&self.record.command).ok()
+        });
+        if let Some(stats) = stats {
+            preview.push('\n');
+            preview.push_str(&format_stats(&stats));
+        }
+
         ItemPreview::Text(preview)
     }
 }
@@ -121,16 +185,24 @@ impl SkimItem for SavedCommandItem {
             self.command.tags.join(", ")
         };
 
-        let desc_str = self.command.description
-            .as_ref()
-            .map(|d| d.as_str())
-            .unwrap_or("None");
+        let desc_str = self.command.description.as_deref().unwrap_or("None");
 
-        let preview = format!(
+        let mut preview = format!(
             "Command: {}\nTags: {}\nDescription: {}\nCreated: {}",
             self.command.command, tags_str, desc_str, created
         );
 
+        let placeholders = self.command.placeholders();
+        if !placeholders.is_empty() {
+            preview.push_str("\n\nPlaceholders:");
+            for placeholder in placeholders {
+                match placeholder.default {
+                    Some(default) => preview.push_str(&format!("\n  {} (default: {})", placeholder.name, default)),
+                    None => preview.push_str(&format!("\n  {}", placeholder.name)),
+                }
+            }
+        }
+
         ItemPreview::Text(preview)
     }
 }
@@ -177,6 +249,53 @@ mod tests {
         assert_eq!(format_duration(0), "0s");
     }
 
+    fn record(status: CommandStatus, exit_code: Option<i32>) -> HistoryRecord {
+        HistoryRecord {
+            command: "make".to_string(),
+            timestamp: 0,
+            duration: 0,
+            exit_code,
+            hostname: None,
+            status,
+        }
+    }
+
+    #[test]
+    fn test_status_marker_flags_failures() {
+        assert_eq!(status_marker(&record(CommandStatus::Finished, Some(0))), "");
+        assert_eq!(status_marker(&record(CommandStatus::Finished, None)), "");
+        assert_eq!(status_marker(&record(CommandStatus::Finished, Some(2))), "[exit 2] ");
+        assert_eq!(status_marker(&record(CommandStatus::Finished, Some(-1))), "[exit -1] ");
+    }
+
+    #[test]
+    fn test_status_marker_unfinished() {
+        assert_eq!(status_marker(&record(CommandStatus::Running, None)), "[running] ");
+        assert_eq!(status_marker(&record(CommandStatus::Unknown, None)), "[unknown exit] ");
+    }
+
+    #[test]
+    fn test_format_stats_lists_every_directory() {
+        let stats = CommandStats {
+            runs: 3,
+            first_seen: 0,
+            last_seen: 0,
+            avg_duration: Some(89.6),
+            sessions: 2,
+            last_session: Some("abc".to_string()),
+            directories: vec![(Some("/repo".to_string()), 2), (None, 1)],
+            exit_codes: vec![(Some(0), 2), (Some(1), 1)],
+            hosts: vec![(Some("laptop".to_string()), 3)],
+        };
+
+        let out = format_stats(&stats);
+        assert!(out.starts_with("Runs: 3 (avg 1m) across 2 session(s)\n"));
+        assert!(out.contains("Last session: abc\n"));
+        assert!(out.contains("Directories:\n      2  /repo\n      1  -\n"));
+        assert!(out.contains("Exit codes:\n      2  0\n      1  1\n"));
+        assert!(out.contains("Hosts:\n      3  laptop\n"));
+    }
+
     #[test]
     fn test_format_age_zero_timestamp() {
         assert_eq!(format_age(0), "unknown");
@@ -0,0 +1,251 @@
+use serde::{Deserialize, Serialize};
+
+use crate::db::SavedCommand;
+
+/// A saved-command library as written by `saved export`: one `[[command]]`
+/// table per saved command.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Library {
+    #[serde(default, rename = "command")]
+    pub commands: Vec<LibraryEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LibraryEntry {
+    pub command: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// What `saved import` does with a command that is already saved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeStrategy {
+    /// Replace the description and tags with the file's.
+    Overwrite,
+    /// Keep the saved description unless it has none, and add the file's tags.
+    Keep,
+    /// Leave the saved command alone.
+    Skip,
+}
+
+impl MergeStrategy {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "overwrite" => Some(MergeStrategy::Overwrite),
+            "keep" => Some(MergeStrategy::Keep),
+            "skip" => Some(MergeStrategy::Skip),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Change {
+    Add(LibraryEntry),
+    Update { before: LibraryEntry, after: LibraryEntry },
+}
+
+impl Library {
+    pub fn from_saved(saved: Vec<SavedCommand>) -> Self {
+        let mut commands: Vec<LibraryEntry> = saved
+            .into_iter()
+            .map(|cmd| LibraryEntry {
+                command: cmd.command,
+                description: cmd.description,
+                tags: sorted(cmd.tags),
+            })
+            .collect();
+        // Sorted so the file diffs cleanly when kept in git.
+        commands.sort_by(|a, b| a.command.cmp(&b.command));
+        Library { commands }
+    }
+
+    /// The saves needed to merge this library into `existing`. Commands that
+    /// would end up unchanged are left out.
+    pub fn plan(&self, existing: &[SavedCommand], strategy: MergeStrategy) -> Vec<Change> {
+        let mut changes: Vec<Change> = Vec::new();
+
+        for entry in &self.commands {
+            let command = entry.command.trim();
+            if command.is_empty() {
+                continue;
+            }
+            let incoming = LibraryEntry {
+                command: command.to_string(),
+                description: entry.description.clone().filter(|d| !d.is_empty()),
+                tags: sorted(entry.tags.clone()),
+            };
+
+            // A command listed twice in the file: the later entry wins.
+            changes.retain(|c| match c {
+                Change::Add(e) | Change::Update { after: e, .. } => e.command != incoming.command,
+            });
+
+            let Some(saved) = existing.iter().find(|s| s.command == incoming.command) else {
+                changes.push(Change::Add(incoming));
+                continue;
+            };
+            let before = LibraryEntry {
+                command: saved.command.clone(),
+                description: saved.description.clone(),
+                tags: sorted(saved.tags.clone()),
+            };
+
+            let after = match strategy {
+                MergeStrategy::Overwrite => incoming,
+                MergeStrategy::Keep => LibraryEntry {
+                    command: before.command.clone(),
+                    description: before.description.clone().or(incoming.description),
+                    tags: sorted(before.tags.iter().cloned().chain(incoming.tags).collect()),
+                },
+                MergeStrategy::Skip => continue,
+            };
+
+            if after != before {
+                changes.push(Change::Update { before, after });
+            }
+        }
+
+        changes
+    }
+}
+
+impl std::fmt::Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Change::Add(entry) => {
+                write!(f, "+ {}", entry.command)?;
+                if let Some(ref d) = entry.description {
+                    write!(f, "\n    description: {}", d)?;
+                }
+                if !entry.tags.is_empty() {
+                    write!(f, "\n    tags: {}", entry.tags.join(", "))?;
+                }
+                Ok(())
+            }
+            Change::Update { before, after } => {
+                write!(f, "~ {}", after.command)?;
+                if before.description != after.description {
+                    write!(
+                        f,
+                        "\n    description: {} -> {}",
+                        before.description.as_deref().unwrap_or("(none)"),
+                        after.description.as_deref().unwrap_or("(none)"),
+                    )?;
+                }
+                if before.tags != after.tags {
+                    write!(f, "\n    tags: [{}] -> [{}]", before.tags.join(", "), after.tags.join(", "))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn sorted(mut tags: Vec<String>) -> Vec<String> {
+    tags.retain(|t| !t.trim().is_empty());
+    for tag in tags.iter_mut() {
+        *tag = tag.trim().to_string();
+    }
+    tags.sort();
+    tags.dedup();
+    tags
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn saved(command: &str, description: Option<&str>, tags: &[&str]) -> SavedCommand {
+        SavedCommand {
+            id: 1,
+            command: command.to_string(),
+            description: description.map(str::to_string),
+            created_at: 0,
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+        }
+    }
+
+    fn library(toml: &str) -> Library {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let lib = Library::from_saved(vec![
+            saved("ls -la", None, &[]),
+            saved("docker ps -a", Some("List \"all\" containers"), &["ops", "docker"]),
+        ]);
+        let text = toml::to_string(&lib).unwrap();
+        let parsed: Library = toml::from_str(&text).unwrap();
+
+        assert_eq!(parsed.commands, lib.commands);
+        assert_eq!(parsed.commands[0].command, "docker ps -a");
+        assert_eq!(parsed.commands[0].tags, vec!["docker", "ops"]);
+    }
+
+    #[test]
+    fn test_plan_strategies() {
+        let existing = vec![saved("make", Some("build"), &["dev"]), saved("ls", None, &[])];
+        let lib = library(
+            r#"
+            [[command]]
+            command = "make"
+            description = "build everything"
+            tags = ["ci"]
+
+            [[command]]
+            command = "ls"
+
+            [[command]]
+            command = "git pull"
+            tags = ["git"]
+            "#,
+        );
+
+        let overwrite = lib.plan(&existing, MergeStrategy::Overwrite);
+        assert_eq!(overwrite.len(), 2);
+        assert_eq!(
+            overwrite[0],
+            Change::Update {
+                before: LibraryEntry { command: "make".into(), description: Some("build".into()), tags: vec!["dev".into()] },
+                after: LibraryEntry { command: "make".into(), description: Some("build everything".into()), tags: vec!["ci".into()] },
+            }
+        );
+        assert!(matches!(&overwrite[1], Change::Add(e) if e.command == "git pull"));
+
+        let keep = lib.plan(&existing, MergeStrategy::Keep);
+        match &keep[0] {
+            Change::Update { after, .. } => {
+                assert_eq!(after.description.as_deref(), Some("build"));
+                assert_eq!(after.tags, vec!["ci", "dev"]);
+            }
+            other => panic!("unexpected change {:?}", other),
+        }
+
+        let skip = lib.plan(&existing, MergeStrategy::Skip);
+        assert_eq!(skip.len(), 1);
+        assert!(matches!(&skip[0], Change::Add(_)));
+    }
+
+    #[test]
+    fn test_plan_later_duplicate_wins() {
+        let lib = library(
+            r#"
+            [[command]]
+            command = "make"
+            tags = ["a"]
+
+            [[command]]
+            command = " make "
+            tags = ["b"]
+            "#,
+        );
+
+        let changes = lib.plan(&[], MergeStrategy::Overwrite);
+        assert_eq!(changes.len(), 1);
+        assert!(matches!(&changes[0], Change::Add(e) if e.tags == vec!["b"]));
+    }
+}
@@ -3,12 +3,23 @@ use crossterm::event::{KeyCode, KeyModifiers};
 use skim::prelude::*;
 
 mod config;
+mod daemon;
 mod db;
+mod export;
+mod ignore;
 mod item;
+mod library;
+mod secrets;
+mod shell;
 mod stream;
+mod template;
 use config::Config;
-use db::Database;
-use stream::StreamingSearch;
+use db::{Database, ForgetPattern, HistoryEntry, HistoryFilter, HistoryFormat, TagQuery};
+use export::ExportFormat;
+use ignore::IgnoreRules;
+use library::{Change, Library, LibraryEntry, MergeStrategy};
+use secrets::{SecretPolicy, SecretScanner};
+use stream::{ExitFilter, SearchParams, StreamingSearch};
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -19,16 +30,31 @@ struct Cli {
     #[arg(long)]
     mode: Option<String>,
 
+    #[arg(long)]
+    rank: Option<String>,
+
     #[arg(long)]
     limit: Option<u32>,
+
+    #[arg(long)]
+    query: Option<String>,
 }
 
 #[derive(Subcommand)]
 enum Commands {
-    Init,
+    Init {
+        /// Print the integration script for zsh, bash or fish
+        shell: Option<String>,
+        /// Key that opens the picker, in the shell's notation ("" to leave unbound)
+        #[arg(long, requires = "shell")]
+        bind: Option<String>,
+    },
     Import {
         #[arg(short, long)]
         path: Option<String>,
+        /// History format: zsh, bash, fish, atuin, mcfly, histdb or auto
+        #[arg(short, long, default_value = "auto")]
+        format: String,
     },
     Save {
         command: String,
@@ -38,12 +64,165 @@ enum Commands {
         description: Option<String>,
     },
     ListSaved {
-        #[arg(short, long)]
+        /// Tags to match: "a,b" for either, "+a" to require, "-a" to exclude
+        #[arg(short, long, allow_hyphen_values = true)]
         tags: Option<String>,
     },
     DeleteSaved {
         id: i64,
     },
+    /// Change a saved command's text, description or tags
+    EditSaved {
+        id: i64,
+        #[arg(short, long)]
+        command: Option<String>,
+        /// New description ("" to remove it)
+        #[arg(short, long)]
+        description: Option<String>,
+        /// Replace the tags ("" to remove them all)
+        #[arg(short, long)]
+        tags: Option<String>,
+        /// Edit the entry as TOML in $EDITOR (the default when nothing else is given)
+        #[arg(short, long)]
+        editor: bool,
+    },
+    /// Share the saved-command library as a TOML file
+    Saved {
+        #[command(subcommand)]
+        action: SavedAction,
+    },
+    /// Delete commands from history, e.g. a pasted password
+    Forget {
+        /// The command to delete; with --prefix or --regex, a pattern
+        pattern: String,
+        /// Delete every command starting with the pattern
+        #[arg(long, conflicts_with = "regex")]
+        prefix: bool,
+        /// Delete every command the pattern matches anywhere
+        #[arg(long)]
+        regex: bool,
+        /// Don't ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Look for passwords, tokens and keys in the recorded history
+    Audit {
+        /// Mask the secrets found in place
+        #[arg(long)]
+        redact: bool,
+    },
+    /// List, rename, merge and clean up tags
+    Tags {
+        #[command(subcommand)]
+        action: TagsAction,
+    },
+    Record {
+        command: Option<String>,
+        #[arg(long, default_value_t = 0, allow_negative_numbers = true)]
+        exit_code: i32,
+        #[arg(long)]
+        cwd: Option<String>,
+        #[arg(long)]
+        session: Option<String>,
+        #[arg(long)]
+        start: Option<i64>,
+        #[arg(long, default_value_t = 0)]
+        duration: i64,
+    },
+    RecordStart {
+        command: Option<String>,
+        #[arg(long)]
+        cwd: Option<String>,
+        #[arg(long)]
+        session: Option<String>,
+        #[arg(long)]
+        start: Option<i64>,
+    },
+    RecordFinish {
+        #[arg(long)]
+        session: Option<String>,
+        #[arg(long)]
+        start: i64,
+        #[arg(long, default_value_t = 0, allow_negative_numbers = true)]
+        exit_code: i32,
+        #[arg(long, default_value_t = 0)]
+        duration: i64,
+    },
+    Daemon {
+        #[arg(long)]
+        print_socket_path: bool,
+    },
+    /// Export history and saved commands
+    Export {
+        /// jsonl, csv, zsh or bash
+        #[arg(short, long, default_value = "jsonl")]
+        format: String,
+        /// Export the saved commands instead of history (jsonl or csv)
+        #[arg(long)]
+        saved: bool,
+        /// Only commands started at or after this time (epoch or YYYY-MM-DD [HH:MM:SS])
+        #[arg(long)]
+        since: Option<String>,
+        /// Only commands started before this time
+        #[arg(long)]
+        until: Option<String>,
+        #[arg(long)]
+        host: Option<String>,
+        /// Only commands run in this directory or below it
+        #[arg(long)]
+        cwd: Option<String>,
+        #[arg(long)]
+        session: Option<String>,
+        /// Write to a file instead of stdout
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum SavedAction {
+    /// Write saved commands as TOML
+    Export {
+        /// Tags to match, as for list-saved
+        #[arg(short, long, allow_hyphen_values = true)]
+        tags: Option<String>,
+        /// Write to a file instead of stdout
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Merge saved commands from a TOML file
+    Import {
+        path: String,
+        /// What to do with commands already saved: overwrite, keep or skip
+        #[arg(short, long, default_value = "overwrite")]
+        strategy: String,
+        /// Show the changes without saving them
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum TagsAction {
+    /// Show each tag with the number of saved commands using it
+    List,
+    Rename {
+        from: String,
+        to: String,
+    },
+    /// Fold tags into another one, keeping their commands tagged
+    Merge {
+        #[arg(required = true)]
+        from: Vec<String>,
+        #[arg(long)]
+        into: String,
+    },
+    /// Remove a tag from every saved command
+    Delete {
+        name: String,
+    },
+    /// Delete tags no saved command uses
+    Prune,
 }
 
 fn main() {
@@ -51,7 +230,25 @@ fn main() {
 
     if let Some(command) = cli.command {
         match command {
-            Commands::Init => match Database::new() {
+            Commands::Init { shell: Some(shell), bind } => {
+                let bin = std::env::current_exe()
+                    .map(|p| p.to_string_lossy().to_string())
+                    .unwrap_or_else(|_| "hindsight".to_string());
+                let script = match shell::init_script(&shell, &bin, bind.as_deref()) {
+                    Some(script) => script,
+                    None => {
+                        eprintln!("Unsupported shell '{}'. Valid shells: zsh, bash, fish", shell);
+                        std::process::exit(1);
+                    }
+                };
+                if let Err(e) = Database::new() {
+                    eprintln!("Failed to initialize database: {}", e);
+                    std::process::exit(1);
+                }
+                print!("{}", script);
+                std::process::exit(0);
+            }
+            Commands::Init { shell: None, .. } => match Database::new() {
                 Ok(_) => {
                     println!(
                         "Database initialized successfully at: {:?}",
@@ -64,13 +261,30 @@ fn main() {
                     std::process::exit(1);
                 }
             },
-            Commands::Import { path } => {
+            Commands::Import { path, format } => {
+                let format = match format.as_str() {
+                    "auto" => None,
+                    other => match HistoryFormat::parse(other) {
+                        Some(f) => Some(f),
+                        None => {
+                            eprintln!("Invalid format '{}'. Valid formats: zsh, bash, fish, atuin, mcfly, histdb, auto", other);
+                            std::process::exit(1);
+                        }
+                    },
+                };
+
                 let history_path = path
                     .map(std::path::PathBuf::from)
+                    .or_else(|| match format {
+                        Some(format) => format.default_path(),
+                        None => [HistoryFormat::Zsh, HistoryFormat::Bash, HistoryFormat::Fish]
+                            .into_iter()
+                            .filter_map(HistoryFormat::default_path)
+                            .find(|p| p.exists()),
+                    })
                     .unwrap_or_else(|| {
-                        dirs::home_dir()
-                            .expect("could not find home directory")
-                            .join(".zsh_history")
+                        eprintln!("No history file found, pass one with --path");
+                        std::process::exit(1);
                     });
 
                 if !history_path.exists() {
@@ -78,7 +292,12 @@ fn main() {
                     std::process::exit(1);
                 }
 
-                let db = match Database::new() {
+                let format = format.or_else(|| HistoryFormat::sniff(&history_path)).unwrap_or_else(|| {
+                    eprintln!("Can't tell which tool wrote {:?}, pass --format", history_path);
+                    std::process::exit(1);
+                });
+
+                let db = match open_for_recording() {
                     Ok(db) => db,
                     Err(e) => {
                         eprintln!("Failed to open database: {}", e);
@@ -86,11 +305,19 @@ fn main() {
                     }
                 };
 
-                println!("Importing from {:?}...", history_path);
+                println!("Importing {:?} history from {:?}...", format, history_path);
 
-                match db.import_zsh_history(&history_path) {
+                let result = match format {
+                    HistoryFormat::Zsh => db.import_zsh_history(&history_path),
+                    HistoryFormat::Bash => db.import_bash_history(&history_path),
+                    HistoryFormat::Fish => db.import_fish_history(&history_path),
+                    HistoryFormat::Atuin => db.import_atuin_history(&history_path),
+                    HistoryFormat::Mcfly => db.import_mcfly_history(&history_path),
+                    HistoryFormat::Histdb => db.import_histdb_history(&history_path),
+                };
+                match result {
                     Ok(stats) => {
-                        println!("Imported {} commands ({} duplicates skipped)", stats.imported, stats.skipped);
+                        println!("Imported {} commands ({} duplicates or ignored skipped)", stats.imported, stats.skipped);
                         std::process::exit(0);
                     }
                     Err(e) => {
@@ -141,14 +368,9 @@ fn main() {
                     }
                 };
 
-                let tag_filter: Option<Vec<String>> = tags.map(|t| {
-                    t.split(',')
-                        .map(|s| s.trim().to_string())
-                        .filter(|s| !s.is_empty())
-                        .collect()
-                });
+                let query = tags.as_deref().map(TagQuery::parse).unwrap_or_default();
 
-                match db.get_saved_commands(tag_filter) {
+                match db.find_saved_commands(&query) {
                     Ok(commands) => {
                         if commands.is_empty() {
                             println!("No saved commands found");
@@ -198,6 +420,532 @@ fn main() {
                     }
                 }
             }
+            Commands::EditSaved { id, command, description, tags, editor } => {
+                let db = match Database::new() {
+                    Ok(db) => db,
+                    Err(e) => {
+                        eprintln!("Failed to open database: {}", e);
+                        std::process::exit(1);
+                    }
+                };
+
+                let saved = match db.get_saved_command(id) {
+                    Ok(Some(saved)) => saved,
+                    Ok(None) => {
+                        eprintln!("No saved command with ID #{}", id);
+                        std::process::exit(1);
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to read saved command: {}", e);
+                        std::process::exit(1);
+                    }
+                };
+
+                let use_editor = editor || (command.is_none() && description.is_none() && tags.is_none());
+                let mut entry = LibraryEntry {
+                    command: command.unwrap_or(saved.command),
+                    description: description.or(saved.description),
+                    tags: tags
+                        .map(|t| t.split(',').map(|s| s.to_string()).collect())
+                        .unwrap_or(saved.tags),
+                };
+
+                if use_editor {
+                    entry = match edit_in_editor(id, &entry) {
+                        Ok(entry) => entry,
+                        Err(e) => {
+                            eprintln!("Failed to edit saved command: {}", e);
+                            std::process::exit(1);
+                        }
+                    };
+                }
+
+                let command = entry.command.trim();
+                if command.is_empty() {
+                    eprintln!("Saved command can't be empty");
+                    std::process::exit(1);
+                }
+                let description = entry.description.as_deref().map(str::trim).filter(|d| !d.is_empty());
+                let tags: Vec<String> = entry
+                    .tags
+                    .iter()
+                    .map(|t| t.trim().to_string())
+                    .filter(|t| !t.is_empty())
+                    .collect();
+
+                match db.update_saved_command(id, command, description, tags) {
+                    Ok(true) => {
+                        println!("Updated saved command #{}", id);
+                        std::process::exit(0);
+                    }
+                    Ok(false) => {
+                        eprintln!("No saved command with ID #{}", id);
+                        std::process::exit(1);
+                    }
+                    Err(rusqlite::Error::SqliteFailure(err, _))
+                        if err.code == rusqlite::ErrorCode::ConstraintViolation =>
+                    {
+                        eprintln!("Another saved command is already \"{}\"", command);
+                        std::process::exit(1);
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to update saved command: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            Commands::Saved { action } => {
+                let db = match Database::new() {
+                    Ok(db) => db,
+                    Err(e) => {
+                        eprintln!("Failed to open database: {}", e);
+                        std::process::exit(1);
+                    }
+                };
+
+                match action {
+                    SavedAction::Export { tags, output } => {
+                        let query = tags.as_deref().map(TagQuery::parse).unwrap_or_default();
+
+                        let saved = match db.find_saved_commands(&query) {
+                            Ok(saved) => saved,
+                            Err(e) => {
+                                eprintln!("Failed to list saved commands: {}", e);
+                                std::process::exit(1);
+                            }
+                        };
+                        let text = match toml::to_string(&Library::from_saved(saved)) {
+                            Ok(text) => text,
+                            Err(e) => {
+                                eprintln!("Failed to export saved commands: {}", e);
+                                std::process::exit(1);
+                            }
+                        };
+
+                        match output {
+                            Some(path) => {
+                                if let Err(e) = std::fs::write(&path, text) {
+                                    eprintln!("Failed to write {}: {}", path, e);
+                                    std::process::exit(1);
+                                }
+                            }
+                            None => print!("{}", text),
+                        }
+                        std::process::exit(0);
+                    }
+                    SavedAction::Import { path, strategy, dry_run } => {
+                        let Some(strategy) = MergeStrategy::parse(&strategy) else {
+                            eprintln!("Invalid strategy '{}'. Valid strategies: overwrite, keep, skip", strategy);
+                            std::process::exit(1);
+                        };
+
+                        let library: Library = match std::fs::read_to_string(&path)
+                            .map_err(|e| e.to_string())
+                            .and_then(|text| toml::from_str(&text).map_err(|e| e.to_string()))
+                        {
+                            Ok(library) => library,
+                            Err(e) => {
+                                eprintln!("Failed to read {}: {}", path, e);
+                                std::process::exit(1);
+                            }
+                        };
+
+                        let existing = match db.get_saved_commands(None) {
+                            Ok(saved) => saved,
+                            Err(e) => {
+                                eprintln!("Failed to list saved commands: {}", e);
+                                std::process::exit(1);
+                            }
+                        };
+
+                        let changes = library.plan(&existing, strategy);
+                        for change in &changes {
+                            println!("{}", change);
+                        }
+
+                        let added = changes.iter().filter(|c| matches!(c, Change::Add(_))).count();
+                        let updated = changes.len() - added;
+                        if dry_run {
+                            println!("Would add {} and update {} saved commands", added, updated);
+                            std::process::exit(0);
+                        }
+
+                        let result = db.transaction(|db| {
+                            for change in &changes {
+                                let (Change::Add(entry) | Change::Update { after: entry, .. }) = change;
+                                db.save_command(&entry.command, entry.description.as_deref(), entry.tags.clone())?;
+                            }
+                            Ok(())
+                        });
+
+                        match result {
+                            Ok(()) => {
+                                println!("Added {} and updated {} saved commands", added, updated);
+                                std::process::exit(0);
+                            }
+                            Err(e) => {
+                                eprintln!("Failed to import saved commands: {}", e);
+                                std::process::exit(1);
+                            }
+                        }
+                    }
+                }
+            }
+            Commands::Forget { pattern, prefix, regex, yes } => {
+                let db = match Database::new() {
+                    Ok(db) => db,
+                    Err(e) => {
+                        eprintln!("Failed to open database: {}", e);
+                        std::process::exit(1);
+                    }
+                };
+
+                let pattern = if regex {
+                    match regex::Regex::new(&pattern) {
+                        Ok(re) => ForgetPattern::Regex(re),
+                        Err(e) => {
+                            eprintln!("Invalid regex: {}", e);
+                            std::process::exit(1);
+                        }
+                    }
+                } else if prefix {
+                    ForgetPattern::Prefix(pattern)
+                } else {
+                    ForgetPattern::Exact(pattern)
+                };
+
+                let matched = match db.matching_commands(&pattern) {
+                    Ok(matched) => matched,
+                    Err(e) => {
+                        eprintln!("Failed to search history: {}", e);
+                        std::process::exit(1);
+                    }
+                };
+                if matched.is_empty() {
+                    println!("No matching commands in history");
+                    std::process::exit(0);
+                }
+
+                let runs: i64 = matched.iter().map(|(_, runs)| runs).sum();
+                for (command, count) in &matched {
+                    println!("{:>5}  {}", count, command);
+                }
+                if !yes {
+                    use std::io::Write;
+                    print!("Delete {} entries of {} commands? [y/N] ", runs, matched.len());
+                    let _ = std::io::stdout().flush();
+                    let mut answer = String::new();
+                    let _ = std::io::stdin().read_line(&mut answer);
+                    if !matches!(answer.trim(), "y" | "Y" | "yes") {
+                        println!("Nothing deleted");
+                        std::process::exit(0);
+                    }
+                }
+
+                let commands: Vec<String> = matched.into_iter().map(|(command, _)| command).collect();
+                match db.forget_commands(&commands).and_then(|deleted| db.vacuum().map(|_| deleted)) {
+                    Ok(deleted) => {
+                        println!("Deleted {} history entries", deleted);
+                        std::process::exit(0);
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to delete history: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            Commands::Audit { redact } => {
+                let db = match Database::new() {
+                    Ok(db) => db,
+                    Err(e) => {
+                        eprintln!("Failed to open database: {}", e);
+                        std::process::exit(1);
+                    }
+                };
+
+                let scanner = SecretScanner::new(&Config::load().secrets.patterns);
+                let (rows, flagged) = match db.history_rows(&HistoryFilter::default()).and_then(|rows| Ok((rows, db.flagged_history()?))) {
+                    Ok(found) => found,
+                    Err(e) => {
+                        eprintln!("Failed to read history: {}", e);
+                        std::process::exit(1);
+                    }
+                };
+
+                let mut found = 0;
+                let mut redactions = Vec::new();
+                for (id, entry) in &rows {
+                    let findings = scanner.find(&entry.command);
+                    let flag = flagged.iter().find(|(flagged_id, _)| flagged_id == id);
+                    if findings.is_empty() && flag.is_none() {
+                        continue;
+                    }
+                    found += 1;
+
+                    let mut kinds: Vec<&str> = findings.iter().map(|f| f.kind.as_str()).collect();
+                    if let Some((_, flagged_kinds)) = flag {
+                        kinds.extend(flagged_kinds.split(','));
+                    }
+                    kinds.sort();
+                    kinds.dedup();
+                    let masked = secrets::mask_findings(&entry.command, &findings);
+                    println!("#{:<6} {:<24} {}", id, kinds.join(","), masked.replace('\n', " "));
+
+                    if redact && !findings.is_empty() {
+                        redactions.push((*id, masked));
+                    }
+                }
+
+                // One transaction, then compact like `forget`, so the
+                // unmasked text doesn't linger in free pages.
+                let redacted = if redactions.is_empty() {
+                    0
+                } else {
+                    match db.redact_history(&redactions).and_then(|redacted| db.vacuum().map(|_| redacted)) {
+                        Ok(redacted) => redacted,
+                        Err(e) => {
+                            eprintln!("Failed to redact history: {}", e);
+                            std::process::exit(1);
+                        }
+                    }
+                };
+
+                if found == 0 {
+                    println!("No secrets found in {} commands", rows.len());
+                } else if redact {
+                    println!("Masked secrets in {} of {} commands", redacted, found);
+                } else {
+                    println!(
+                        "{} commands contain secrets; `hindsight audit --redact` masks them, `hindsight forget` deletes them",
+                        found
+                    );
+                }
+                std::process::exit(0);
+            }
+            Commands::Tags { action } => {
+                let db = match Database::new() {
+                    Ok(db) => db,
+                    Err(e) => {
+                        eprintln!("Failed to open database: {}", e);
+                        std::process::exit(1);
+                    }
+                };
+
+                let result = match action {
+                    TagsAction::List => db.tag_counts().map(|counts| {
+                        if counts.is_empty() {
+                            println!("No tags found");
+                        }
+                        let width = counts.iter().map(|(name, _)| name.chars().count()).max().unwrap_or(0);
+                        for (name, count) in counts {
+                            println!("{:<width$}  {}", name, count, width = width);
+                        }
+                    }),
+                    TagsAction::Rename { from, to } => match db.rename_tag(&from, to.trim()) {
+                        Ok(true) => {
+                            println!("Renamed tag '{}' to '{}'", from, to.trim());
+                            Ok(())
+                        }
+                        Ok(false) => {
+                            eprintln!("No tag named '{}'", from);
+                            std::process::exit(1);
+                        }
+                        Err(rusqlite::Error::SqliteFailure(err, _))
+                            if err.code == rusqlite::ErrorCode::ConstraintViolation =>
+                        {
+                            eprintln!("Tag '{}' already exists; use `tags merge {} --into {}` to combine them", to.trim(), from, to.trim());
+                            std::process::exit(1);
+                        }
+                        Err(e) => Err(e),
+                    },
+                    TagsAction::Merge { from, into } => db.merge_tags(&from, into.trim()).map(|merged| {
+                        println!("Merged {} tags into '{}'", merged, into.trim());
+                    }),
+                    TagsAction::Delete { name } => match db.delete_tag(&name) {
+                        Ok(true) => {
+                            println!("Deleted tag '{}'", name);
+                            Ok(())
+                        }
+                        Ok(false) => {
+                            eprintln!("No tag named '{}'", name);
+                            std::process::exit(1);
+                        }
+                        Err(e) => Err(e),
+                    },
+                    TagsAction::Prune => db.prune_tags().map(|pruned| {
+                        println!("Deleted {} unused tags", pruned);
+                    }),
+                };
+
+                if let Err(e) = result {
+                    eprintln!("Failed to update tags: {}", e);
+                    std::process::exit(1);
+                }
+                std::process::exit(0);
+            }
+            Commands::Record {
+                command,
+                exit_code,
+                cwd,
+                session,
+                start,
+                duration,
+            } => {
+                let start_ts = start.unwrap_or_else(|| chrono::Utc::now().timestamp() - duration.max(0));
+                let entry = history_entry(command, cwd, session, start_ts, Some(exit_code), Some(duration));
+
+                let db = match open_for_recording() {
+                    Ok(db) => db,
+                    Err(e) => {
+                        eprintln!("Failed to open database: {}", e);
+                        std::process::exit(1);
+                    }
+                };
+
+                match db.record_command(&entry) {
+                    Ok(_) => std::process::exit(0),
+                    Err(e) => {
+                        eprintln!("Failed to record command: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            Commands::RecordStart {
+                command,
+                cwd,
+                session,
+                start,
+            } => {
+                let start_ts = start.unwrap_or_else(|| chrono::Utc::now().timestamp());
+                let entry = history_entry(command, cwd, session, start_ts, None, None);
+
+                let db = match open_for_recording() {
+                    Ok(db) => db,
+                    Err(e) => {
+                        eprintln!("Failed to open database: {}", e);
+                        std::process::exit(1);
+                    }
+                };
+
+                match db.record_command(&entry) {
+                    Ok(_) => std::process::exit(0),
+                    Err(e) => {
+                        eprintln!("Failed to record command: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            Commands::RecordFinish {
+                session,
+                start,
+                exit_code,
+                duration,
+            } => {
+                let session = session
+                    .or_else(|| std::env::var("HINDSIGHT_SESSION").ok())
+                    .unwrap_or_default();
+
+                let db = match Database::new() {
+                    Ok(db) => db,
+                    Err(e) => {
+                        eprintln!("Failed to open database: {}", e);
+                        std::process::exit(1);
+                    }
+                };
+
+                match db.finish_command(&session, start, exit_code, duration) {
+                    Ok(_) => std::process::exit(0),
+                    Err(e) => {
+                        eprintln!("Failed to record command: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            Commands::Export { format, saved, since, until, host, cwd, session, output } => {
+                let Some(format) = ExportFormat::parse(&format) else {
+                    eprintln!("Invalid format '{}'. Valid formats: jsonl, csv, zsh, bash", format);
+                    std::process::exit(1);
+                };
+
+                let parse_time = |value: Option<String>| {
+                    value.map(|v| {
+                        export::parse_time(&v).unwrap_or_else(|| {
+                            eprintln!("Invalid time '{}'. Use epoch seconds or YYYY-MM-DD [HH:MM:SS]", v);
+                            std::process::exit(1);
+                        })
+                    })
+                };
+                let filter = HistoryFilter {
+                    since: parse_time(since),
+                    until: parse_time(until),
+                    hostname: host,
+                    cwd,
+                    session,
+                };
+
+                let db = match Database::new() {
+                    Ok(db) => db,
+                    Err(e) => {
+                        eprintln!("Failed to open database: {}", e);
+                        std::process::exit(1);
+                    }
+                };
+
+                let mut out: Box<dyn std::io::Write> = match output {
+                    Some(path) => match std::fs::File::create(&path) {
+                        Ok(file) => Box::new(std::io::BufWriter::new(file)),
+                        Err(e) => {
+                            eprintln!("Failed to create {}: {}", path, e);
+                            std::process::exit(1);
+                        }
+                    },
+                    None => Box::new(std::io::BufWriter::new(std::io::stdout().lock())),
+                };
+
+                // jsonl holds everything; the other formats hold one table.
+                let include_history = !saved;
+                let include_saved = saved || format == ExportFormat::Jsonl;
+
+                let result = (|| -> Result<(), Box<dyn std::error::Error>> {
+                    if include_history {
+                        export::write_history(&mut out, format, &db.history_rows(&filter)?, &db.history_paths()?)?;
+                    }
+                    if include_saved {
+                        export::write_saved(&mut out, format, &db.get_tags()?, &db.get_saved_commands(None)?)?;
+                    }
+                    out.flush()?;
+                    Ok(())
+                })();
+
+                match result {
+                    Ok(()) => std::process::exit(0),
+                    Err(e) => {
+                        eprintln!("Failed to export: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            Commands::Daemon { print_socket_path } => {
+                let path = match daemon::socket_path() {
+                    Ok(path) => path,
+                    Err(e) => {
+                        eprintln!("No safe place for the daemon socket: {}", e);
+                        std::process::exit(1);
+                    }
+                };
+                if print_socket_path {
+                    println!("{}", path.display());
+                    std::process::exit(0);
+                }
+
+                match daemon::run(&path) {
+                    Ok(()) => std::process::exit(0),
+                    Err(e) => {
+                        eprintln!("Daemon failed: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
         }
     }
 
@@ -208,10 +956,19 @@ fn main() {
         .or(config.default_mode)
         .unwrap_or_else(|| "global".to_string());
 
-    if !["global", "session", "cwd", "saved"].contains(&mode.as_str()) {
+    if !["global", "session", "cwd", "tree", "host", "saved"].contains(&mode.as_str()) {
         eprintln!("Warning: invalid mode '{}', using 'global'", mode);
         mode = "global".to_string();
     }
+    let mut rank = cli
+        .rank
+        .or(config.default_rank.clone())
+        .unwrap_or_else(|| "recent".to_string());
+
+    if !["recent", "frecency"].contains(&rank.as_str()) {
+        eprintln!("Warning: invalid rank '{}', using 'recent'", rank);
+        rank = "recent".to_string();
+    }
     let limit = cli.limit.or(config.default_limit).unwrap_or(1000);
 
     let current_session = std::env::var("HINDSIGHT_SESSION").unwrap_or_default();
@@ -220,29 +977,94 @@ fn main() {
             .map(|p| p.to_string_lossy().to_string())
             .unwrap_or_default()
     });
+    let current_tree_root = stream::tree_root(&current_cwd, config.tree_git_root.unwrap_or(true));
+    let current_hostname = hostname::get()
+        .map(|h| h.to_string_lossy().to_string())
+        .unwrap_or_else(|_| "unknown".to_string());
+
+    let show_preview = config.show_preview.unwrap_or(false);
 
     let mut selected_cmd: Option<String> = None;
     let mut edit = false;
+    let mut exit_filter = match config.default_exit_filter.as_deref() {
+        None => ExitFilter::Is(0),
+        Some(value) => ExitFilter::parse(value).unwrap_or_else(|| {
+            eprintln!("Warning: invalid exit filter '{}', using 'all'", value);
+            ExitFilter::All
+        }),
+    };
+    let mut search_query = None;
+    let mut tags = TagQuery::default();
+    // What was typed before leaving skim to prompt, restored when it reopens.
+    let mut typed_query: Option<String> = None;
+    if let Some(query) = cli.query {
+        let (text, filter) = stream::extract_exit_filter(&query);
+        let (text, tag_query) = stream::extract_tag_query(&text);
+        exit_filter = filter.unwrap_or(exit_filter);
+        tags = tag_query.unwrap_or(tags);
+        search_query = Some(text).filter(|q| !q.trim().is_empty());
+    }
 
     loop {
-        let header = format!("Mode: {}", mode);
+        let mut header = format!("Mode: {}", mode);
+        if mode == "tree" {
+            header.push_str(&format!(" ({})", current_tree_root));
+        }
+        if mode != "saved" {
+            if rank != "recent" {
+                header.push_str(&format!(" | Rank: {}", rank));
+            }
+            if exit_filter != ExitFilter::All {
+                header.push_str(&format!(" | Exit: {}", exit_filter));
+            }
+            match &search_query {
+                Some(q) => header.push_str(&format!(" | Search: {}", q)),
+                None => header.push_str(" | ctrl-s: search all history"),
+            }
+        } else if !tags.is_empty() {
+            header.push_str(&format!(" | Tags: {}", tags));
+        }
         let height = config.height.as_deref().unwrap_or("100%").to_string();
+        let preview_window = if show_preview { "right:50%" } else { "right:50%:hidden" };
         let options = SkimOptionsBuilder::default()
             .height(height)
             .multi(false)
             .reverse(true)
-            .bind(vec!["tab:accept".to_string(), "ctrl-r:accept".to_string()])
+            .bind(vec![
+                "tab:accept".to_string(),
+                "ctrl-r:accept".to_string(),
+                "ctrl-s:accept".to_string(),
+                "alt-e:accept".to_string(),
+                "alt-t:accept".to_string(),
+                "alt-s:accept".to_string(),
+                "alt-x:accept".to_string(),
+                "alt-p:toggle-preview".to_string(),
+            ])
+            .preview(Some(String::new()))
+            .preview_window(preview_window.to_string())
             .header(Some(header))
+            .query(typed_query.take().or_else(|| search_query.clone()))
+            // A ctrl-s search is already ranked by the database; keep that
+            // order rather than re-sorting by fuzzy score.
+            .no_sort(search_query.is_some() && mode != "saved")
             .build()
             .unwrap();
 
-        let search = StreamingSearch::new(
-            mode.clone(),
+        let search = StreamingSearch::new(SearchParams {
+            mode: mode.clone(),
+            rank: rank.clone(),
             limit,
-            current_session.clone(),
-            current_cwd.clone(),
-        );
+            session: current_session.clone(),
+            cwd: current_cwd.clone(),
+            tree_root: current_tree_root.clone(),
+            hostname: current_hostname.clone(),
+            query: search_query.clone(),
+            exit_filter,
+            tags: tags.clone(),
+            weights: config.frecency.clone(),
+        });
 
+        let shown = search.shown_ids();
         let items = search.into_receiver();
 
         match Skim::run_with(options, Some(items)) {
@@ -262,10 +1084,45 @@ fn main() {
                     mode = match mode.as_str() {
                         "global" => "session".to_string(),
                         "session" => "cwd".to_string(),
-                        "cwd" => "saved".to_string(),
+                        "cwd" => "tree".to_string(),
+                        "tree" => "host".to_string(),
+                        "host" => "saved".to_string(),
                         _ => "global".to_string(),
                     };
                     continue;
+                } else if key.code == KeyCode::Char('s') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                    let (text, filter) = stream::extract_exit_filter(&output.query);
+                    let (text, tag_query) = stream::extract_tag_query(&text);
+                    exit_filter = filter.unwrap_or(exit_filter);
+                    tags = tag_query.unwrap_or(tags);
+                    search_query = Some(text).filter(|q| !q.trim().is_empty());
+                    continue;
+                } else if key.code == KeyCode::Char('e') && key.modifiers.contains(KeyModifiers::ALT) {
+                    exit_filter = exit_filter.next();
+                    continue;
+                } else if key.code == KeyCode::Char('s') && key.modifiers.contains(KeyModifiers::ALT) {
+                    if mode != "saved" {
+                        if let Some(item) = output.selected_items.first() {
+                            save_from_picker(&item.output());
+                        }
+                    }
+                    typed_query = Some(output.query.clone());
+                    continue;
+                } else if key.code == KeyCode::Char('x') && key.modifiers.contains(KeyModifiers::ALT) {
+                    if mode != "saved" {
+                        if let Some(item) = output.selected_items.first() {
+                            let command = item.output().to_string();
+                            let id = shown.lock().ok().and_then(|ids| ids.get(&command).copied());
+                            delete_from_picker(&command, id);
+                        }
+                    }
+                    typed_query = Some(output.query.clone());
+                    continue;
+                } else if key.code == KeyCode::Char('t') && key.modifiers.contains(KeyModifiers::ALT) {
+                    if mode == "saved" {
+                        tags = next_tag_facet(&tags);
+                    }
+                    continue;
                 } else {
                     if let Some(item) = output.selected_items.first() {
                         selected_cmd = Some(item.output().to_string());
@@ -277,11 +1134,236 @@ fn main() {
         }
     }
 
-    if let Some(cmd) = selected_cmd {
+    if let Some(mut cmd) = selected_cmd {
+        // Saved templates are always handed back for editing: filled in
+        // after prompting, with the cursor on the first placeholder left
+        // unanswered, if any.
+        let mut cursor = None;
+        if mode == "saved" && !template::placeholders(&cmd).is_empty() {
+            if !edit {
+                if let Some(filled) = fill_template(&cmd) {
+                    cmd = filled;
+                }
+            }
+            cursor = template::first_placeholder(&cmd);
+            edit = true;
+        }
+
         print!("__HINDSIGHT_MODE__{}__", mode);
         if edit {
             print!("__HINDSIGHT_EDIT__");
         }
+        if let Some(cursor) = cursor {
+            print!("__HINDSIGHT_CURSOR__{}__", cursor);
+        }
         print!("{}", cmd);
     }
 }
+
+/// Steps the `saved` mode tag filter through each tag in use, then back to
+/// no filter. A filter typed with `tag:` restarts from the first tag.
+fn next_tag_facet(current: &TagQuery) -> TagQuery {
+    let names: Vec<String> = Database::open_read_only()
+        .and_then(|db| db.tag_counts())
+        .map(|counts| counts.into_iter().filter(|(_, n)| *n > 0).map(|(name, _)| name).collect())
+        .unwrap_or_default();
+
+    let next = match current.any.as_slice() {
+        [tag] if current.all.is_empty() && current.none.is_empty() => {
+            names.iter().skip_while(|n| *n != tag).nth(1)
+        }
+        _ => names.first(),
+    };
+    match next {
+        Some(tag) => TagQuery { any: vec![tag.clone()], ..TagQuery::default() },
+        None => TagQuery::default(),
+    }
+}
+
+/// Opens the database to write history to, applying the configured
+/// ignore rules and secret checks.
+fn open_for_recording() -> rusqlite::Result<Database> {
+    let config = Config::load();
+    Ok(Database::new()?
+        .with_ignore_rules(IgnoreRules::new(&config.ignore))
+        .with_secrets(SecretPolicy::new(&config.secrets)))
+}
+
+/// Prompts on the terminal for a saved command's placeholder values,
+/// suggesting ones used before. Runs in the alternate screen so the shell's
+/// prompt is left as it was. `None` if there's no terminal or input ends.
+fn fill_template(command: &str) -> Option<String> {
+    let db = Database::new().ok();
+    let values = on_tty(|input, output| {
+        template::prompt(input, output, command, |name| {
+            db.as_ref()
+                .and_then(|db| db.placeholder_values(name, 5).ok())
+                .unwrap_or_default()
+        })
+    })?;
+    let values = values.ok()??;
+
+    if let Some(db) = &db {
+        for (name, value) in &values {
+            if !value.is_empty() {
+                let _ = db.remember_placeholder_value(name, value);
+            }
+        }
+    }
+    Some(template::fill(command, &values))
+}
+
+/// Asks on the terminal for a description and tags, then saves `command`.
+/// A command that is already saved offers its current values as defaults.
+fn save_from_picker(command: &str) {
+    use std::io::Write;
+
+    let Ok(db) = Database::new() else {
+        return;
+    };
+    let existing = db
+        .find_saved_commands(&TagQuery::default())
+        .ok()
+        .and_then(|saved| saved.into_iter().find(|s| s.command == command));
+    let current_tags = existing.as_ref().map(|s| s.tags.join(",")).filter(|t| !t.is_empty());
+    let current_description = existing.as_ref().and_then(|s| s.description.clone());
+
+    let _ = on_tty(|input, output| -> std::io::Result<()> {
+        writeln!(output, "Save: {}", command)?;
+        let Some(description) = template::ask(input, output, "Description", current_description.as_deref())? else {
+            return Ok(());
+        };
+        let Some(tags) = template::ask(input, output, "Tags (comma-separated)", current_tags.as_deref())? else {
+            return Ok(());
+        };
+
+        let tags: Vec<String> = tags
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect();
+        let description = Some(description.trim()).filter(|d| !d.is_empty());
+        if let Err(e) = db.save_command(command, description, tags) {
+            writeln!(output, "Failed to save command: {}", e)?;
+            output.flush()?;
+            std::thread::sleep(std::time::Duration::from_secs(2));
+        }
+        Ok(())
+    });
+}
+
+/// Asks whether to delete the run of `command` the picker showed (`id`)
+/// or every run of it, then deletes it.
+fn delete_from_picker(command: &str, id: Option<i64>) {
+    use std::io::Write;
+
+    let Ok(db) = Database::new() else {
+        return;
+    };
+    let runs = db
+        .matching_commands(&ForgetPattern::Exact(command.to_string()))
+        .ok()
+        .and_then(|matched| matched.first().map(|(_, runs)| *runs))
+        .unwrap_or(0);
+    if runs == 0 {
+        return;
+    }
+
+    let answer = on_tty(|input, output| {
+        writeln!(output, "Delete: {}", command)?;
+        let question = if runs == 1 {
+            "Delete it? [y/N]".to_string()
+        } else {
+            format!("Delete [t]his run or [a]ll {} runs? (enter cancels)", runs)
+        };
+        template::ask(input, output, &question, None)
+    });
+    let answer = answer.and_then(|a| a.ok()).flatten().unwrap_or_default();
+
+    let result = match (answer.trim(), id) {
+        ("t" | "y", Some(id)) => db.delete_history_entry(id).map(|_| ()),
+        ("a", _) | ("y", None) => db.forget_commands(&[command.to_string()]).map(|_| ()),
+        _ => return,
+    };
+    if let Err(e) = result {
+        eprintln!("Failed to delete command: {}", e);
+    }
+}
+
+/// Runs `f` against the terminal in the alternate screen, so the shell
+/// prompt underneath is left as it was.
+fn on_tty<T>(f: impl FnOnce(&mut std::io::BufReader<std::fs::File>, &mut std::fs::File) -> T) -> Option<T> {
+    use std::io::Write;
+
+    let tty = std::fs::OpenOptions::new().read(true).write(true).open("/dev/tty").ok()?;
+    let mut output = tty.try_clone().ok()?;
+    let mut input = std::io::BufReader::new(tty);
+
+    let _ = write!(output, "\x1b[?1049h\x1b[H");
+    let result = f(&mut input, &mut output);
+    let _ = write!(output, "\x1b[?1049l");
+    Some(result)
+}
+
+/// Opens `entry` as a TOML snippet in `$VISUAL`/`$EDITOR` and reads it
+/// back once the editor exits.
+fn edit_in_editor(id: i64, entry: &LibraryEntry) -> Result<LibraryEntry, Box<dyn std::error::Error>> {
+    // Created exclusively and readable only by us; removed when dropped.
+    let mut file = tempfile::Builder::new()
+        .prefix(&format!("hindsight-saved-{}-", id))
+        .suffix(".toml")
+        .tempfile()?;
+    std::io::Write::write_all(&mut file, toml::to_string(entry)?.as_bytes())?;
+    std::io::Write::flush(&mut file)?;
+    let path = file.path().to_path_buf();
+
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    // Through the shell so an EDITOR with arguments ("code --wait") works.
+    let status = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor))
+        .arg("sh")
+        .arg(&path)
+        .status();
+    let text = std::fs::read_to_string(&path);
+    drop(file);
+
+    if !status?.success() {
+        return Err("editor exited with an error".into());
+    }
+    Ok(toml::from_str(&text?)?)
+}
+
+fn history_entry(
+    command: Option<String>,
+    cwd: Option<String>,
+    session: Option<String>,
+    start_ts: i64,
+    exit_code: Option<i32>,
+    duration: Option<i64>,
+) -> HistoryEntry {
+    let command = command.unwrap_or_else(|| {
+        let mut buf = String::new();
+        if let Err(e) = std::io::Read::read_to_string(&mut std::io::stdin(), &mut buf) {
+            eprintln!("Failed to read command from stdin: {}", e);
+            std::process::exit(1);
+        }
+        buf
+    });
+
+    HistoryEntry {
+        command,
+        exit_code,
+        cwd: cwd.or_else(|| std::env::var("PWD").ok()),
+        hostname: hostname::get()
+            .map(|h| h.to_string_lossy().to_string())
+            .unwrap_or_else(|_| "unknown".to_string()),
+        session: session
+            .or_else(|| std::env::var("HINDSIGHT_SESSION").ok())
+            .unwrap_or_default(),
+        start_ts,
+        duration,
+    }
+}
@@ -0,0 +1,126 @@
+const ZSH: &str = include_str!("../shell/hindsight.zsh");
+const BASH: &str = include_str!("../shell/hindsight.bash");
+const FISH: &str = include_str!("../shell/hindsight.fish");
+
+/// Returns the integration script for `shell`, prefixed with the variables
+/// it reads: the binary to run and, if given, the key that opens the picker
+/// in the shell's own notation (`^R` for zsh, `\C-r` for bash, `\cr` for
+/// fish). An empty key leaves the picker unbound.
+pub fn init_script(shell: &str, bin: &str, bind: Option<&str>) -> Option<String> {
+    let (script, fish) = match shell {
+        "zsh" => (ZSH, false),
+        "bash" => (BASH, false),
+        "fish" => (FISH, true),
+        _ => return None,
+    };
+    let set = |name: &str, value: &str| {
+        if fish {
+            format!("set -g {} {}\n", name, fish_quote(value))
+        } else {
+            format!("{}={}\n", name, posix_quote(value))
+        }
+    };
+
+    let mut out = set("HINDSIGHT_BIN", bin);
+    if let Some(bind) = bind {
+        out.push_str(&set("HINDSIGHT_BIND", bind));
+    }
+    out.push_str(script);
+    Some(out)
+}
+
+fn posix_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+fn fish_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\\', r"\\").replace('\'', r"\'"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_init_script_sets_binary_and_key() {
+        let script = init_script("zsh", "/opt/it's here/hindsight", Some("^G")).unwrap();
+        assert!(script.starts_with("HINDSIGHT_BIN='/opt/it'\\''s here/hindsight'\nHINDSIGHT_BIND='^G'\n"));
+        assert!(script.ends_with(ZSH));
+
+        let script = init_script("bash", "/usr/bin/hindsight", None).unwrap();
+        assert!(script.starts_with("HINDSIGHT_BIN='/usr/bin/hindsight'\nif "));
+    }
+
+    #[test]
+    fn test_init_script_fish_quoting() {
+        let script = init_script("fish", "/opt/it's/hindsight", Some(r"\cg")).unwrap();
+        assert!(script.starts_with("set -g HINDSIGHT_BIN '/opt/it\\'s/hindsight'\nset -g HINDSIGHT_BIND '\\\\cg'\n"));
+    }
+
+    /// Types `keys` into an interactive bash running the integration
+    /// under `script`, with a stand-in binary that logs the record calls
+    /// and answers the picker with `echo picked`. `None` without bash or
+    /// script.
+    fn run_bash(keys: &[&str]) -> Option<Vec<String>> {
+        use std::io::Write;
+        use std::process::{Command, Stdio};
+
+        let found = |tool: &str| Command::new(tool).arg("--version").output().is_ok_and(|o| o.status.success());
+        if !found("bash") || !found("script") {
+            return None;
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let bin = dir.path().join("hindsight");
+        let log = dir.path().join("log");
+        std::fs::write(
+            &bin,
+            "#!/bin/sh\ncase \"$1\" in record-start) shift 8; printf '%s\\n' \"$*\" >> \"$HINDSIGHT_LOG\" ;;\n\
+             record-*) ;;\n*) printf 'echo picked' ;;\nesac\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&bin, std::os::unix::fs::PermissionsExt::from_mode(0o755)).unwrap();
+        let rc = dir.path().join("rc");
+        let script = init_script("bash", &bin.to_string_lossy(), None).unwrap();
+        std::fs::write(&rc, format!("HISTCONTROL=ignoreboth\nPS1='$ '\n{}", script)).unwrap();
+
+        let mut child = Command::new("timeout")
+            .arg("20")
+            .arg("script")
+            .arg("-qec")
+            .arg(format!("bash --noprofile --rcfile '{}' -i", rc.display()))
+            .arg("/dev/null")
+            .env("HINDSIGHT_LOG", &log)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()
+            .unwrap();
+        let mut stdin = child.stdin.take().unwrap();
+        for keys in keys.iter().chain(&["exit\r"]) {
+            std::thread::sleep(std::time::Duration::from_millis(500));
+            stdin.write_all(keys.as_bytes()).unwrap();
+            stdin.flush().unwrap();
+        }
+        std::thread::sleep(std::time::Duration::from_millis(500));
+        drop(stdin);
+        child.wait().unwrap();
+
+        let log = std::fs::read_to_string(&log).unwrap_or_default();
+        Some(log.lines().map(str::to_string).collect())
+    }
+
+    #[test]
+    fn test_bash_records_commands() {
+        // The picker is on \C-r; ` export TOKEN=x` is left out of history
+        // by HISTCONTROL, and so must not be recorded as `export A=1` again.
+        let Some(recorded) = run_bash(&["export A=1\r", " export TOKEN=x\r", "true\r", "true\r", "\x12"]) else {
+            return;
+        };
+        assert_eq!(recorded, vec!["export A=1", "true", "true", "echo picked", "exit"]);
+    }
+
+    #[test]
+    fn test_init_script_unknown_shell() {
+        assert!(init_script("tcsh", "hindsight", None).is_none());
+    }
+}
@@ -1,39 +1,136 @@
 use rusqlite::Connection;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::sync::Mutex;
 use std::thread;
 use skim::prelude::*;
 
-use crate::db::{Database, HistoryRecord, SavedCommand};
+use crate::config::FrecencyWeights;
+use crate::db::{CommandStatus, Database, HistoryRecord, SavedCommand, TagQuery};
 use crate::item::{HistoryItem, SavedCommandItem};
 
+/// How long an unfinished command may still be running. Past this its shell
+/// was most likely killed before the finish hook ran.
+const RUNNING_CUTOFF: i64 = 24 * 60 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitFilter {
+    All,
+    Is(i32),
+    IsNot(i32),
+}
+
+impl ExitFilter {
+    /// Parses the value of an `exit:` filter: `all`, `0`, `!0`, `130`, ...
+    pub fn parse(value: &str) -> Option<Self> {
+        if value == "all" {
+            return Some(ExitFilter::All);
+        }
+        match value.strip_prefix('!') {
+            Some(code) => code.parse().ok().map(ExitFilter::IsNot),
+            None => value.parse().ok().map(ExitFilter::Is),
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            ExitFilter::All => ExitFilter::Is(0),
+            ExitFilter::Is(0) => ExitFilter::IsNot(0),
+            _ => ExitFilter::All,
+        }
+    }
+}
+
+impl fmt::Display for ExitFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExitFilter::All => write!(f, "all"),
+            ExitFilter::Is(code) => write!(f, "{}", code),
+            ExitFilter::IsNot(code) => write!(f, "!{}", code),
+        }
+    }
+}
+
+/// Pulls `exit:<filter>` terms out of a typed query. Returns the rest of
+/// the query and the last valid filter, if any.
+pub fn extract_exit_filter(query: &str) -> (String, Option<ExitFilter>) {
+    let mut filter = None;
+    let mut rest = Vec::new();
+
+    for term in query.split_whitespace() {
+        match term.strip_prefix("exit:").and_then(ExitFilter::parse) {
+            Some(f) => filter = Some(f),
+            None => rest.push(term),
+        }
+    }
+
+    (rest.join(" "), filter)
+}
+
+/// Pulls `tag:<terms>` out of a typed query, with terms written as for
+/// `--tags`. Returns the rest of the query and the tags asked for, if any
+/// `tag:` term was given; a bare `tag:` asks for none.
+pub fn extract_tag_query(query: &str) -> (String, Option<TagQuery>) {
+    let mut tags: Option<TagQuery> = None;
+    let mut rest = Vec::new();
+
+    for term in query.split_whitespace() {
+        match term.strip_prefix("tag:") {
+            Some(terms) => tags.get_or_insert_with(TagQuery::default).extend(TagQuery::parse(terms)),
+            None => rest.push(term),
+        }
+    }
+
+    (rest.join(" "), tags)
+}
+
+#[derive(Clone)]
+pub struct SearchParams {
+    pub mode: String,
+    pub rank: String,
+    pub limit: u32,
+    pub session: String,
+    pub cwd: String,
+    pub tree_root: String,
+    pub hostname: String,
+    pub query: Option<String>,
+    pub exit_filter: ExitFilter,
+    /// Only used in `saved` mode.
+    pub tags: TagQuery,
+    pub weights: FrecencyWeights,
+}
+
+/// The history id behind each command listed so far. Rows are grouped by
+/// command, so this is the run the picker shows for it.
+pub type ShownIds = Arc<Mutex<HashMap<String, i64>>>;
+
 pub struct StreamingSearch {
     receiver: SkimItemReceiver,
+    shown: ShownIds,
     _handle: thread::JoinHandle<()>,
 }
 
 impl StreamingSearch {
-    pub fn new(
-        mode: String,
-        limit: u32,
-        session: String,
-        cwd: String,
-    ) -> Self {
+    pub fn new(params: SearchParams) -> Self {
         let (sender, receiver) = unbounded();
+        let shown = ShownIds::default();
         
+        let thread_shown = shown.clone();
         let handle = thread::spawn(move || {
-            let _ = Self::stream_results(&mode, limit, &session, &cwd, sender);
+            let _ = Self::stream_results(&params, &thread_shown, sender);
         });
         
         StreamingSearch {
             receiver,
+            shown,
             _handle: handle,
         }
     }
     
     fn stream_results(
-        mode: &str,
-        limit: u32,
-        session: &str,
-        cwd: &str,
+        params: &SearchParams,
+        shown: &ShownIds,
         sender: Sender<Arc<dyn SkimItem>>,
     ) -> rusqlite::Result<()> {
         let db_path = Database::db_path()?;
@@ -45,80 +142,565 @@ impl StreamingSearch {
              PRAGMA mmap_size = 268435456;"
         )?;
 
-        if mode == "saved" {
-            let mut stmt = conn.prepare_cached(
-                "SELECT sc.id, sc.command, sc.description, sc.created_at, GROUP_CONCAT(t.name) as tags
-                 FROM saved_commands sc
-                 LEFT JOIN command_tags ct ON sc.id = ct.command_id
-                 LEFT JOIN tags t ON ct.tag_id = t.id
-                 GROUP BY sc.id
-                 ORDER BY sc.created_at DESC
-                 LIMIT ?1"
-            )?;
-            let mut rows = stmt.query([limit])?;
-
-            while let Some(row) = rows.next()? {
-                let tags_str: Option<String> = row.get(4)?;
-                let tags: Vec<String> = tags_str
-                    .map(|s| s.split(',').map(|t| t.to_string()).collect())
-                    .unwrap_or_default();
-
-                let saved_cmd = SavedCommand {
-                    id: row.get(0)?,
-                    command: row.get(1)?,
-                    description: row.get(2)?,
-                    created_at: row.get(3)?,
-                    tags,
-                };
-
-                let item = Arc::new(SavedCommandItem { command: saved_cmd }) as Arc<dyn SkimItem>;
-                if sender.send(item).is_err() {
-                    break;
+        if params.mode == "saved" {
+            Self::stream_saved(&conn, params.limit, &params.tags, &sender)
+        } else {
+            // Previews query while this thread is still streaming, so they
+            // get a connection of their own.
+            let preview_db = Database::open_read_only().ok().map(|db| Arc::new(Mutex::new(db)));
+            Self::stream_history(&conn, params, preview_db, shown, &sender)
+        }
+    }
+
+    fn stream_saved(
+        conn: &Connection,
+        limit: u32,
+        tags: &TagQuery,
+        sender: &Sender<Arc<dyn SkimItem>>,
+    ) -> rusqlite::Result<()> {
+        let (condition, tag_params) = tags.sql();
+        let mut stmt = conn.prepare_cached(&format!(
+            "SELECT sc.id, sc.command, sc.description, sc.created_at, GROUP_CONCAT(t.name) as tags
+             FROM saved_commands sc
+             LEFT JOIN command_tags ct ON sc.id = ct.command_id
+             LEFT JOIN tags t ON ct.tag_id = t.id
+             WHERE {}
+             GROUP BY sc.id
+             ORDER BY sc.created_at DESC
+             LIMIT ?",
+            condition
+        ))?;
+        let mut params: Vec<&dyn rusqlite::ToSql> = tag_params.iter().map(|t| t as &dyn rusqlite::ToSql).collect();
+        params.push(&limit);
+        let mut rows = stmt.query(params.as_slice())?;
+
+        while let Some(row) = rows.next()? {
+            let tags_str: Option<String> = row.get(4)?;
+            let tags: Vec<String> = tags_str
+                .map(|s| s.split(',').map(|t| t.to_string()).collect())
+                .unwrap_or_default();
+
+            let saved_cmd = SavedCommand {
+                id: row.get(0)?,
+                command: row.get(1)?,
+                description: row.get(2)?,
+                created_at: row.get(3)?,
+                tags,
+            };
+
+            let item = Arc::new(SavedCommandItem { command: saved_cmd }) as Arc<dyn SkimItem>;
+            if sender.send(item).is_err() {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn stream_history(
+        conn: &Connection,
+        params: &SearchParams,
+        preview_db: Option<Arc<Mutex<Database>>>,
+        shown: &ShownIds,
+        sender: &Sender<Arc<dyn SkimItem>>,
+    ) -> rusqlite::Result<()> {
+        // Everything under the root sorts between `root` and `root0` since '0'
+        // follows '/', which keeps `tree` mode a range scan on idx_history_cwd.
+        let tree_root = params.tree_root.trim_end_matches('/');
+        let tree_children = format!("{}/", tree_root);
+        let tree_end = format!("{}0", tree_root);
+
+        let mut from = "history h";
+        let mut order = "start_ts DESC";
+        let mut conditions: Vec<&str> = Vec::new();
+        let mut args: Vec<&dyn rusqlite::ToSql> = Vec::new();
+
+        let frecency = params.rank == "frecency";
+        let score = if frecency {
+            // Context arguments come first since the score is in the SELECT list.
+            args.push(&params.cwd);
+            args.push(&params.session);
+            args.push(&params.hostname);
+            order = "score DESC, start_ts DESC";
+            frecency_score(&params.weights, chrono::Utc::now().timestamp())
+        } else {
+            "0".to_string()
+        };
+
+        let (substring, prefix) = params.query.as_deref().map(fts_expressions).unwrap_or_default();
+        if let Some(ref expr) = substring {
+            from = "history_trigram m JOIN history h ON h.id = m.rowid";
+            if !frecency {
+                order = "m.rank, start_ts DESC";
+            }
+            conditions.push("history_trigram MATCH ?");
+            args.push(expr);
+        }
+        if let Some(ref expr) = prefix {
+            if substring.is_some() {
+                conditions.push("h.id IN (SELECT rowid FROM history_fts WHERE history_fts MATCH ?)");
+            } else {
+                from = "history_fts m JOIN history h ON h.id = m.rowid";
+                if !frecency {
+                    order = "m.rank, start_ts DESC";
                 }
+                conditions.push("history_fts MATCH ?");
+            }
+            args.push(expr);
+        }
+
+        match params.mode.as_str() {
+            "session" => {
+                conditions.push("h.session = ?");
+                args.push(&params.session);
             }
+            "cwd" => {
+                conditions.push("h.cwd = ?");
+                args.push(&params.cwd);
+            }
+            "tree" => {
+                conditions.push("(h.cwd >= ? AND h.cwd < ? AND (h.cwd = ? OR h.cwd >= ?))");
+                args.push(&tree_root);
+                args.push(&tree_end);
+                args.push(&tree_root);
+                args.push(&tree_children);
+            }
+            "host" => {
+                conditions.push("h.hostname = ?");
+                args.push(&params.hostname);
+            }
+            _ => {}
+        }
+        match params.exit_filter {
+            ExitFilter::All => {}
+            // Unfinished runs have no exit code yet; keep them visible so
+            // the running and unknown markers show under any code filter.
+            ExitFilter::Is(ref code) => {
+                conditions.push("(h.exit_code = ? OR h.duration IS NULL)");
+                args.push(code);
+            }
+            ExitFilter::IsNot(ref code) => {
+                conditions.push("(h.exit_code != ? OR h.duration IS NULL)");
+                args.push(code);
+            }
+        }
+        args.push(&params.limit);
+
+        let where_clause = if conditions.is_empty() {
+            String::new()
         } else {
-            let (query, params): (&str, Vec<&dyn rusqlite::ToSql>) = match mode {
-                "session" => (
-                    "SELECT command, MAX(start_ts) as start_ts, MAX(duration) as duration
-                     FROM history WHERE session = ?1
-                     GROUP BY command ORDER BY start_ts DESC LIMIT ?2",
-                    vec![&session, &limit]
-                ),
-                "cwd" => (
-                    "SELECT command, MAX(start_ts) as start_ts, MAX(duration) as duration
-                     FROM history WHERE cwd = ?1
-                     GROUP BY command ORDER BY start_ts DESC LIMIT ?2",
-                    vec![&cwd, &limit]
-                ),
-                _ => (
-                    "SELECT command, MAX(start_ts) as start_ts, MAX(duration) as duration
-                     FROM history
-                     GROUP BY command ORDER BY start_ts DESC LIMIT ?1",
-                    vec![&limit]
-                ),
+            format!("WHERE {}", conditions.join(" AND "))
+        };
+        let query = format!(
+            "SELECT h.id, h.command, MAX(h.start_ts) as start_ts, h.duration, h.session, h.exit_code, h.hostname, {} as score
+             FROM {} {}
+             GROUP BY h.command ORDER BY {} LIMIT ?",
+            score, from, where_clause, order
+        );
+
+        let mut stmt = conn.prepare_cached(&query)?;
+        let mut rows = stmt.query(args.as_slice())?;
+
+        while let Some(row) = rows.next()? {
+            let id: i64 = row.get(0)?;
+            let duration: Option<i64> = row.get(3)?;
+            let status = match duration {
+                Some(_) => CommandStatus::Finished,
+                None => {
+                    let session: Option<String> = row.get(4)?;
+                    let start_ts: i64 = row.get(2)?;
+                    Self::unfinished_status(conn, id, session.as_deref(), start_ts, chrono::Utc::now().timestamp())?
+                }
             };
 
-            let mut stmt = conn.prepare_cached(query)?;
-            let mut rows = stmt.query(params.as_slice())?;
+            let record = HistoryRecord {
+                command: row.get(1)?,
+                timestamp: row.get(2)?,
+                duration: duration.unwrap_or(0),
+                exit_code: row.get(5)?,
+                hostname: row.get(6)?,
+                status,
+            };
 
-            while let Some(row) = rows.next()? {
-                let record = HistoryRecord {
-                    command: row.get(0)?,
-                    timestamp: row.get(1)?,
-                    duration: row.get(2)?,
-                };
+            if let Ok(mut shown) = shown.lock() {
+                shown.insert(record.command.clone(), id);
+            }
 
-                let item = Arc::new(HistoryItem { record }) as Arc<dyn SkimItem>;
-                if sender.send(item).is_err() {
-                    break;
-                }
+            let item = Arc::new(HistoryItem { record, db: preview_db.clone() }) as Arc<dyn SkimItem>;
+            if sender.send(item).is_err() {
+                break;
             }
         }
 
         Ok(())
     }
     
+    /// A command that never got finished is still running only if it
+    /// started within `RUNNING_CUTOFF` and nothing else has been recorded in
+    /// its session since.
+    fn unfinished_status(
+        conn: &Connection,
+        id: i64,
+        session: Option<&str>,
+        start_ts: i64,
+        now: i64,
+    ) -> rusqlite::Result<CommandStatus> {
+        if now - start_ts > RUNNING_CUTOFF {
+            return Ok(CommandStatus::Unknown);
+        }
+        let later: bool = conn.prepare_cached(
+            "SELECT EXISTS(SELECT 1 FROM history WHERE session = ?1 AND id > ?2)"
+        )?.query_row(rusqlite::params![session, id], |row| row.get(0))?;
+
+        Ok(if later { CommandStatus::Unknown } else { CommandStatus::Running })
+    }
+
+    pub fn shown_ids(&self) -> ShownIds {
+        self.shown.clone()
+    }
+
     pub fn into_receiver(self) -> SkimItemReceiver {
         self.receiver
     }
-}
\ No newline at end of file
+}
+
+/// Scope for `tree` mode: the enclosing git work tree when asked for and
+/// found, otherwise the current directory itself.
+pub fn tree_root(cwd: &str, use_git_root: bool) -> String {
+    if use_git_root {
+        let git_root = Path::new(cwd)
+            .ancestors()
+            .find(|dir| dir.join(".git").exists());
+        if let Some(root) = git_root {
+            return root.to_string_lossy().to_string();
+        }
+    }
+    cwd.to_string()
+}
+
+/// Scores a command group from 0 to the sum of the weights. Frequency
+/// saturates as a command is run more often and recency halves after a day.
+/// The `?` placeholders take the current cwd, session and hostname.
+fn frecency_score(weights: &FrecencyWeights, now: i64) -> String {
+    let w = |v: f64| if v.is_finite() { v } else { 0.0 };
+    format!(
+        "({:?} * COUNT(*) / (COUNT(*) + 5.0)
+          + {:?} / (1.0 + ({} - MAX(h.start_ts)) / 86400.0)
+          + {:?} * (TOTAL(h.cwd IS ?) > 0)
+          + {:?} * (TOTAL(h.session IS ?) > 0)
+          + {:?} * (TOTAL(h.hostname IS ?) > 0)
+          + {:?} * COALESCE(AVG(h.exit_code = 0), 0))",
+        w(weights.frequency),
+        w(weights.recency),
+        now,
+        w(weights.cwd),
+        w(weights.session),
+        w(weights.host),
+        w(weights.success),
+    )
+}
+
+/// Splits a typed query into FTS5 expressions. Terms of three or more
+/// characters become substring matches against the trigram index, shorter
+/// ones become prefix matches against `history_fts`.
+fn fts_expressions(query: &str) -> (Option<String>, Option<String>) {
+    let mut substring = Vec::new();
+    let mut prefix = Vec::new();
+
+    for term in query.split_whitespace() {
+        let quoted = format!("\"{}\"", term.replace('"', "\"\""));
+        if term.chars().count() >= 3 {
+            substring.push(quoted);
+        } else if term.chars().any(char::is_alphanumeric) {
+            prefix.push(format!("{}*", quoted));
+        }
+    }
+
+    let join = |terms: Vec<String>| (!terms.is_empty()).then(|| terms.join(" AND "));
+    (join(substring), join(prefix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::HistoryEntry;
+
+    fn params(mode: &str, rank: &str) -> SearchParams {
+        SearchParams {
+            mode: mode.to_string(),
+            rank: rank.to_string(),
+            limit: 100,
+            session: "current".to_string(),
+            cwd: "/repo".to_string(),
+            tree_root: "/repo".to_string(),
+            hostname: "laptop".to_string(),
+            query: None,
+            exit_filter: ExitFilter::All,
+            tags: TagQuery::default(),
+            weights: FrecencyWeights::default(),
+        }
+    }
+
+    fn record(db: &Database, command: &str, cwd: &str, start_ts: i64, exit_code: i32) {
+        record_on(db, "laptop", command, cwd, start_ts, exit_code);
+    }
+
+    fn record_on(db: &Database, hostname: &str, command: &str, cwd: &str, start_ts: i64, exit_code: i32) {
+        db.record_command(&HistoryEntry {
+            command: command.to_string(),
+            exit_code: Some(exit_code),
+            cwd: Some(cwd.to_string()),
+            hostname: hostname.to_string(),
+            session: "old".to_string(),
+            start_ts,
+            duration: Some(1),
+        }).unwrap();
+    }
+
+    fn commands(db: &Database, params: &SearchParams) -> Vec<String> {
+        let (sender, receiver) = unbounded();
+        StreamingSearch::stream_history(db.connection(), params, None, &ShownIds::default(), &sender).unwrap();
+        drop(sender);
+        receiver.iter().map(|item| item.text().to_string()).collect()
+    }
+
+    #[test]
+    fn test_recent_rank_orders_by_last_run() {
+        let db = Database::in_memory().unwrap();
+        let now = chrono::Utc::now().timestamp();
+        record(&db, "make test", "/repo", now - 300, 0);
+        record(&db, "ls", "/tmp", now - 100, 0);
+        record(&db, "make test", "/repo", now - 200, 0);
+
+        assert_eq!(commands(&db, &params("global", "recent")), vec!["ls", "make test"]);
+        assert_eq!(commands(&db, &params("cwd", "recent")), vec!["make test"]);
+    }
+
+    #[test]
+    fn test_host_mode_filters_by_hostname() {
+        let db = Database::in_memory().unwrap();
+        let now = chrono::Utc::now().timestamp();
+        record_on(&db, "laptop", "brew upgrade", "/repo", now - 30, 0);
+        record_on(&db, "server", "systemctl restart nginx", "/repo", now - 20, 0);
+
+        assert_eq!(commands(&db, &params("host", "recent")), vec!["brew upgrade"]);
+        assert_eq!(commands(&db, &params("global", "recent")).len(), 2);
+    }
+
+    #[test]
+    fn test_tree_mode_includes_descendants_only() {
+        let db = Database::in_memory().unwrap();
+        let now = chrono::Utc::now().timestamp();
+        record(&db, "cargo build", "/repo", now - 50, 0);
+        record(&db, "cargo test", "/repo/src", now - 40, 0);
+        record(&db, "ls", "/repo/src/bin", now - 30, 0);
+        record(&db, "git log", "/repo-old", now - 20, 0);
+        record(&db, "make", "/repository", now - 10, 0);
+
+        assert_eq!(
+            commands(&db, &params("tree", "recent")),
+            vec!["ls", "cargo test", "cargo build"]
+        );
+
+        let mut search = params("tree", "recent");
+        search.tree_root = "/repo/src/".to_string();
+        assert_eq!(commands(&db, &search), vec!["ls", "cargo test"]);
+
+        search.tree_root = "/".to_string();
+        assert_eq!(commands(&db, &search).len(), 5);
+    }
+
+    #[test]
+    fn test_tree_root_finds_enclosing_git_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let nested = dir.path().join("src/bin");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::create_dir(dir.path().join(".git")).unwrap();
+
+        let root = dir.path().to_string_lossy().to_string();
+        let cwd = nested.to_string_lossy().to_string();
+        assert_eq!(tree_root(&cwd, true), root);
+        assert_eq!(tree_root(&cwd, false), cwd);
+    }
+
+    #[test]
+    fn test_frecency_prefers_frequent_successful_local_commands() {
+        let db = Database::in_memory().unwrap();
+        let now = chrono::Utc::now().timestamp();
+        for i in 0..5 {
+            record(&db, "make test", "/repo", now - 3600 - i, 0);
+        }
+        // Each neighbour in the expected order differs by one factor worth
+        // far more than the few seconds between their last runs: run count,
+        // then directory, then exit status.
+        record(&db, "cat notes.txt", "/tmp", now - 10, 0);
+        record(&db, "make broken", "/tmp", now - 30, 1);
+        record(&db, "make deploy", "/repo", now - 60, 0);
+
+        assert_eq!(
+            commands(&db, &params("global", "frecency")),
+            vec!["make test", "make deploy", "cat notes.txt", "make broken"]
+        );
+    }
+
+    #[test]
+    fn test_search_and_frecency_combine() {
+        let db = Database::in_memory().unwrap();
+        let now = chrono::Utc::now().timestamp();
+        record(&db, "git push", "/repo", now - 10, 0);
+        record(&db, "git status", "/repo", now - 20, 0);
+        record(&db, "git status", "/repo", now - 30, 0);
+
+        let mut search = params("global", "frecency");
+        search.query = Some("stat".to_string());
+        assert_eq!(commands(&db, &search), vec!["git status"]);
+    }
+
+    #[test]
+    fn test_exit_filter_restricts_rows() {
+        let db = Database::in_memory().unwrap();
+        let now = chrono::Utc::now().timestamp();
+        record(&db, "make", "/repo", now - 30, 2);
+        record(&db, "make", "/repo", now - 60, 0);
+        record(&db, "ls", "/repo", now - 10, 0);
+
+        let mut search = params("global", "recent");
+        search.exit_filter = ExitFilter::Is(0);
+        assert_eq!(commands(&db, &search), vec!["ls", "make"]);
+
+        search.exit_filter = ExitFilter::IsNot(0);
+        assert_eq!(commands(&db, &search), vec!["make"]);
+
+        search.exit_filter = ExitFilter::Is(2);
+        assert_eq!(commands(&db, &search), vec!["make"]);
+    }
+
+    #[test]
+    fn test_unfinished_status() {
+        let db = Database::in_memory().unwrap();
+        let now = chrono::Utc::now().timestamp();
+        let start = |command: &str, session: &str, start_ts: i64| {
+            db.record_command(&HistoryEntry {
+                command: command.to_string(),
+                exit_code: None,
+                cwd: Some("/repo".to_string()),
+                hostname: "laptop".to_string(),
+                session: session.to_string(),
+                start_ts,
+                duration: None,
+            }).unwrap();
+        };
+        start("sleep 100", "a", now - 60);
+        start("vim", "b", now - 120);
+        start("ls", "b", now - 60);
+        start("tail -f log", "c", now - 3 * 86400);
+
+        let status = |id, session, start_ts| {
+            StreamingSearch::unfinished_status(db.connection(), id, Some(session), start_ts, now).unwrap()
+        };
+        assert_eq!(status(1, "a", now - 60), CommandStatus::Running);
+        assert_eq!(status(2, "b", now - 120), CommandStatus::Unknown);
+        assert_eq!(status(4, "c", now - 3 * 86400), CommandStatus::Unknown);
+    }
+
+    #[test]
+    fn test_default_exit_filter_keeps_unfinished_commands() {
+        let db = Database::in_memory().unwrap();
+        let now = chrono::Utc::now().timestamp();
+        record(&db, "make", "/repo", now - 30, 2);
+        record(&db, "ls", "/repo", now - 20, 0);
+        db.record_command(&HistoryEntry {
+            command: "cargo build".to_string(),
+            exit_code: None,
+            cwd: Some("/repo".to_string()),
+            hostname: "laptop".to_string(),
+            session: "old".to_string(),
+            start_ts: now - 10,
+            duration: None,
+        }).unwrap();
+
+        let mut search = params("global", "recent");
+        search.exit_filter = ExitFilter::Is(0);
+        assert_eq!(commands(&db, &search), vec!["cargo build", "ls"]);
+
+        search.exit_filter = ExitFilter::IsNot(0);
+        assert_eq!(commands(&db, &search), vec!["cargo build", "make"]);
+    }
+
+    #[test]
+    fn test_exit_filter_parse() {
+        assert_eq!(ExitFilter::parse("all"), Some(ExitFilter::All));
+        assert_eq!(ExitFilter::parse("0"), Some(ExitFilter::Is(0)));
+        assert_eq!(ExitFilter::parse("!0"), Some(ExitFilter::IsNot(0)));
+        assert_eq!(ExitFilter::parse("130"), Some(ExitFilter::Is(130)));
+        assert_eq!(ExitFilter::parse("!x"), None);
+        assert_eq!(ExitFilter::parse(""), None);
+    }
+
+    #[test]
+    fn test_exit_filter_cycle() {
+        assert_eq!(ExitFilter::All.next(), ExitFilter::Is(0));
+        assert_eq!(ExitFilter::Is(0).next(), ExitFilter::IsNot(0));
+        assert_eq!(ExitFilter::IsNot(0).next(), ExitFilter::All);
+        assert_eq!(ExitFilter::Is(2).next(), ExitFilter::All);
+    }
+
+    #[test]
+    fn test_extract_exit_filter() {
+        assert_eq!(
+            extract_exit_filter("make exit:!0 test"),
+            ("make test".to_string(), Some(ExitFilter::IsNot(0)))
+        );
+        assert_eq!(extract_exit_filter("exit:0"), (String::new(), Some(ExitFilter::Is(0))));
+        assert_eq!(extract_exit_filter("echo exit:foo"), ("echo exit:foo".to_string(), None));
+    }
+
+    #[test]
+    fn test_extract_tag_query() {
+        let (rest, tags) = extract_tag_query("logs tag:k8s,-old tag:+prod");
+        assert_eq!(rest, "logs");
+        assert_eq!(tags.unwrap().to_string(), "k8s,+prod,-old");
+        assert_eq!(extract_tag_query("tag:"), (String::new(), Some(TagQuery::default())));
+        assert_eq!(extract_tag_query("make"), ("make".to_string(), None));
+    }
+
+    #[test]
+    fn test_saved_mode_filters_by_tags() {
+        let db = Database::in_memory().unwrap();
+        db.save_command("docker ps", None, vec!["docker".to_string(), "prod".to_string()]).unwrap();
+        db.save_command("docker build", None, vec!["docker".to_string()]).unwrap();
+        db.save_command("ls", None, vec![]).unwrap();
+
+        let saved = |tags: &str| {
+            let (sender, receiver) = unbounded();
+            StreamingSearch::stream_saved(db.connection(), 100, &TagQuery::parse(tags), &sender).unwrap();
+            drop(sender);
+            let mut found: Vec<String> = receiver.iter().map(|item| item.output().to_string()).collect();
+            found.sort();
+            found
+        };
+
+        assert_eq!(saved(""), vec!["docker build", "docker ps", "ls"]);
+        assert_eq!(saved("+docker,-prod"), vec!["docker build"]);
+    }
+
+    #[test]
+    fn test_fts_expressions_split_by_length() {
+        let (substring, prefix) = fts_expressions("kubectl -n pods");
+        assert_eq!(substring.as_deref(), Some("\"kubectl\" AND \"pods\""));
+        assert_eq!(prefix.as_deref(), Some("\"-n\"*"));
+    }
+
+    #[test]
+    fn test_fts_expressions_escape_quotes() {
+        let (substring, prefix) = fts_expressions("echo \"hi\"");
+        assert_eq!(substring.as_deref(), Some("\"echo\" AND \"\"\"hi\"\"\""));
+        assert_eq!(prefix, None);
+    }
+
+    #[test]
+    fn test_fts_expressions_skip_punctuation_only_short_terms() {
+        assert_eq!(fts_expressions("| &&"), (None, None));
+        assert_eq!(fts_expressions("   "), (None, None));
+    }
+}
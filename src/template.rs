@@ -0,0 +1,238 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+/// A `{{name}}` or `{{name:default}}` slot in a saved command.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Placeholder {
+    pub name: String,
+    pub default: Option<String>,
+}
+
+/// Finds each `{{...}}` span, yielding its byte range and contents. Names
+/// may not be empty or contain whitespace, so `{{ }}` and shell text such
+/// as `{{a b}}` are left alone.
+fn spans(command: &str) -> impl Iterator<Item = (usize, usize, Placeholder)> + '_ {
+    let mut pos = 0;
+    std::iter::from_fn(move || {
+        while let Some(start) = command[pos..].find("{{").map(|i| pos + i) {
+            let Some(len) = command[start + 2..].find("}}") else {
+                pos = command.len();
+                return None;
+            };
+            let end = start + 2 + len + 2;
+            let inner = &command[start + 2..end - 2];
+            let (name, default) = match inner.split_once(':') {
+                Some((name, default)) => (name, Some(default.to_string())),
+                None => (inner, None),
+            };
+            if name.is_empty() || name.contains(char::is_whitespace) || name.contains('{') {
+                pos = start + 1;
+                continue;
+            }
+            pos = end;
+            return Some((start, end, Placeholder { name: name.to_string(), default }));
+        }
+        None
+    })
+}
+
+/// The distinct placeholders in `command`, in order of first use. A default
+/// given anywhere applies to every use of the name.
+pub fn placeholders(command: &str) -> Vec<Placeholder> {
+    let mut found: Vec<Placeholder> = Vec::new();
+    for (_, _, placeholder) in spans(command) {
+        match found.iter_mut().find(|p| p.name == placeholder.name) {
+            Some(existing) => {
+                if existing.default.is_none() {
+                    existing.default = placeholder.default;
+                }
+            }
+            None => found.push(placeholder),
+        }
+    }
+    found
+}
+
+/// Replaces every placeholder that has a value. Others are left in place.
+pub fn fill(command: &str, values: &HashMap<String, String>) -> String {
+    let mut out = String::with_capacity(command.len());
+    let mut last = 0;
+    for (start, end, placeholder) in spans(command) {
+        if let Some(value) = values.get(&placeholder.name) {
+            out.push_str(&command[last..start]);
+            out.push_str(value);
+            last = end;
+        }
+    }
+    out.push_str(&command[last..]);
+    out
+}
+
+/// Character offset of the first placeholder, for the shell's cursor.
+pub fn first_placeholder(command: &str) -> Option<usize> {
+    spans(command)
+        .next()
+        .map(|(start, _, _)| command[..start].chars().count())
+}
+
+/// Asks for each placeholder's value on `output`, reading lines from
+/// `input`. `suggestions` gives remembered values, most recent first; the
+/// first one, or else the template default, is used for an empty answer.
+/// With neither, the placeholder gets no value, so `fill` leaves it in place
+/// for the user to edit. Returns `None` if input ends before every value is
+/// given.
+pub fn prompt<R: BufRead, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    command: &str,
+    suggestions: impl Fn(&str) -> Vec<String>,
+) -> io::Result<Option<HashMap<String, String>>> {
+    writeln!(output, "{}", command)?;
+
+    let mut values = HashMap::new();
+    for placeholder in placeholders(command) {
+        let recent = suggestions(&placeholder.name);
+        let default = recent.first().cloned().or(placeholder.default.clone());
+
+        write!(output, "{}", placeholder.name)?;
+        if let Some(ref d) = default {
+            write!(output, " [{}]", d)?;
+        }
+        if recent.len() > 1 {
+            write!(output, " (recent: {})", recent.join(", "))?;
+        }
+        write!(output, ": ")?;
+        output.flush()?;
+
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let answer = line.trim_end_matches(['\n', '\r']);
+        let value = if answer.is_empty() { default } else { Some(answer.to_string()) };
+        if let Some(value) = value {
+            values.insert(placeholder.name, value);
+        }
+    }
+    Ok(Some(values))
+}
+
+/// Asks one question on `output` and reads the answer from `input`. An
+/// empty answer gives `default`, or an empty string. Returns `None` at the
+/// end of input.
+pub fn ask<R: BufRead, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    label: &str,
+    default: Option<&str>,
+) -> io::Result<Option<String>> {
+    write!(output, "{}", label)?;
+    if let Some(d) = default {
+        write!(output, " [{}]", d)?;
+    }
+    write!(output, ": ")?;
+    output.flush()?;
+
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    let answer = line.trim_end_matches(['\n', '\r']);
+    if answer.is_empty() {
+        Ok(Some(default.unwrap_or_default().to_string()))
+    } else {
+        Ok(Some(answer.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_placeholders() {
+        let found = placeholders("kubectl logs -n {{namespace:default}} {{pod}} && echo {{pod}}");
+        assert_eq!(
+            found,
+            vec![
+                Placeholder { name: "namespace".to_string(), default: Some("default".to_string()) },
+                Placeholder { name: "pod".to_string(), default: None },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_placeholders_skip_non_names() {
+        assert!(placeholders("awk '{{ print $1 }}' {{}} {{").is_empty());
+        assert_eq!(placeholders("echo {{{x}}}")[0].name, "x");
+    }
+
+    #[test]
+    fn test_fill() {
+        let values = HashMap::from([
+            ("pod".to_string(), "web-1".to_string()),
+            ("ns".to_string(), "prod".to_string()),
+        ]);
+        assert_eq!(
+            fill("kubectl logs -n {{ns:default}} {{pod}} {{pod}} {{other}}", &values),
+            "kubectl logs -n prod web-1 web-1 {{other}}"
+        );
+    }
+
+    #[test]
+    fn test_first_placeholder_counts_chars() {
+        assert_eq!(first_placeholder("echo ü {{x}}"), Some(7));
+        assert_eq!(first_placeholder("echo"), None);
+    }
+
+    #[test]
+    fn test_prompt_uses_answers_then_defaults() {
+        let mut input = Cursor::new("\n\nweb-2\n");
+        let mut output = Vec::new();
+        let suggestions = |name: &str| match name {
+            "ns" => vec!["prod".to_string(), "staging".to_string()],
+            _ => vec![],
+        };
+
+        let values = prompt(&mut input, &mut output, "k -n {{ns}} -c {{ctx:dev}} {{pod}}", suggestions)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(values["ns"], "prod");
+        assert_eq!(values["ctx"], "dev");
+        assert_eq!(values["pod"], "web-2");
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("ns [prod] (recent: prod, staging): "));
+        assert!(output.contains("ctx [dev]: "));
+    }
+
+    #[test]
+    fn test_prompt_leaves_unanswered_placeholder_without_default() {
+        let mut input = Cursor::new("\nweb-2\n");
+        let command = "kubectl logs {{ns}} {{pod}}";
+        let values = prompt(&mut input, &mut Vec::new(), command, |_| vec![]).unwrap().unwrap();
+
+        assert!(!values.contains_key("ns"));
+        let filled = fill(command, &values);
+        assert_eq!(filled, "kubectl logs {{ns}} web-2");
+        assert_eq!(first_placeholder(&filled), Some(13));
+    }
+
+    #[test]
+    fn test_prompt_stops_at_end_of_input() {
+        let mut input = Cursor::new("prod\n");
+        let values = prompt(&mut input, &mut Vec::new(), "{{a}} {{b}}", |_| vec![]).unwrap();
+        assert_eq!(values, None);
+    }
+
+    #[test]
+    fn test_ask() {
+        let mut input = Cursor::new("\nops\n");
+        let mut output = Vec::new();
+        assert_eq!(ask(&mut input, &mut output, "Tags", Some("docker")).unwrap(), Some("docker".to_string()));
+        assert_eq!(ask(&mut input, &mut output, "Description", None).unwrap(), Some("ops".to_string()));
+        assert_eq!(ask(&mut input, &mut output, "Description", None).unwrap(), None);
+        assert_eq!(String::from_utf8(output).unwrap(), "Tags [docker]: Description: Description: ");
+    }
+}